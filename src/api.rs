@@ -0,0 +1,508 @@
+//! A local HTTP server for dashboards and editor plugins, reachable from this computer only.
+//!
+//! - `GET /state` returns the clocks, `GET /history` the recorded sessions.
+//! - `POST /<action>` acts on the main clock and `POST /clocks/<index>/<action>` on any clock,
+//!   with the actions of [`Action`], e.g. `POST /clocks/1/toggle`.
+//! - `GET /events` is a server-sent events stream: a `state` event whenever a clock changes and
+//!   an event named after the hooks' events (`start`, `break_start`, ...) when they happen.
+//!
+//! With a token set, requests need an `Authorization: Bearer <token>` header or a `token`
+//! query parameter, which browsers' `EventSource` can send. Requests must name the listen
+//! address in their `Host`, so that pages of other sites cannot reach the server through a
+//! domain resolving to a loopback address, and requests from web pages only pass from the
+//! origins allowed in the config.
+
+use crate::{history, http};
+use iced::futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Stream, StreamExt,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:7245";
+/// How often an idle event stream is sent a comment, which also notices closed streams.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+/// The most of a request body read and ignored.
+const MAX_BODY: u64 = 64 * 1024;
+/// Events kept for streams that fall behind.
+const MAX_EVENTS: usize = 100;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ApiConfig {
+    /// A loopback address and port, `127.0.0.1:7245` if unset.
+    pub listen: Option<String>,
+    pub token: Option<String>,
+    /// The web page origins allowed to use the API, e.g. `"http://localhost:8080"`.
+    #[serde(default)]
+    pub origins: Vec<String>,
+}
+
+/// What the API shows of a clock.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ClockStatus {
+    /// 0 for the main clock.
+    pub index: usize,
+    pub label: Option<String>,
+    pub mode: &'static str,
+    pub phase: &'static str,
+    pub running: bool,
+    /// The time as the clock shows it.
+    pub display: String,
+    pub planned_secs: Option<u64>,
+    pub elapsed_secs: u64,
+    pub remaining_secs: Option<u64>,
+    pub task: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Toggle,
+    Start,
+    Pause,
+    Reset,
+    ToggleMode,
+    SkipBreak,
+    PostponeBreak,
+    Lap,
+    Longer,
+    Shorter,
+}
+
+impl Action {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "toggle" => Action::Toggle,
+            "start" => Action::Start,
+            "pause" => Action::Pause,
+            "reset" => Action::Reset,
+            "toggle_mode" => Action::ToggleMode,
+            "skip_break" => Action::SkipBreak,
+            "postpone_break" => Action::PostponeBreak,
+            "lap" => Action::Lap,
+            "longer" => Action::Longer,
+            "shorter" => Action::Shorter,
+            _ => return None,
+        })
+    }
+}
+
+/// An action requested for the clock at `clock`.
+#[derive(Debug, Clone, Copy)]
+pub struct Command {
+    pub clock: usize,
+    pub action: Action,
+}
+
+#[derive(Default)]
+struct Published {
+    clocks: Vec<ClockStatus>,
+    /// Bumped on every change, so streams know what they have sent.
+    revision: u64,
+    /// The revision each event was published at, with the event's name and data.
+    events: VecDeque<(u64, &'static str, String)>,
+}
+
+#[derive(Default)]
+struct Shared {
+    published: Mutex<Published>,
+    changed: Condvar,
+}
+
+/// Who may use the API.
+struct Access {
+    address: SocketAddr,
+    token: Option<String>,
+    origins: Vec<String>,
+}
+
+pub struct Api {
+    shared: Arc<Shared>,
+    commands: Mutex<Option<UnboundedReceiver<Command>>>,
+}
+
+impl Api {
+    /// Starts the server, or returns `None` if the address is not a loopback address or is
+    /// taken.
+    pub fn new(config: &ApiConfig) -> Option<Self> {
+        let listen = config.listen.as_deref().unwrap_or(DEFAULT_LISTEN);
+        let address = match http::loopback_address(listen) {
+            Ok(address) => address,
+            Err(e) => {
+                eprintln!("Failed to start the API: {e}");
+                return None;
+            }
+        };
+        let listener = TcpListener::bind(address)
+            .inspect_err(|e| eprintln!("Failed to start the API on {address}: {e}"))
+            .ok()?;
+        let shared = Arc::new(Shared::default());
+        let (sender, receiver) = mpsc::unbounded();
+        let access = Arc::new(Access {
+            address,
+            token: config.token.clone(),
+            origins: config.origins.clone(),
+        });
+        let server = shared.clone();
+        std::thread::spawn(move || {
+            for client in listener.incoming().flatten() {
+                let shared = server.clone();
+                let sender = sender.clone();
+                let access = access.clone();
+                std::thread::spawn(move || {
+                    let _ = serve(client, &shared, &sender, &access);
+                });
+            }
+        });
+        Some(Self {
+            shared,
+            commands: Mutex::new(Some(receiver)),
+        })
+    }
+
+    /// The requested actions. Only the first stream taken yields them.
+    pub fn commands(&self) -> impl Stream<Item = Command> {
+        let receiver = self
+            .commands
+            .lock()
+            .ok()
+            .and_then(|mut commands| commands.take());
+        iced::futures::stream::iter(receiver).flatten()
+    }
+
+    /// Makes the clocks and the events since the last call visible to the clients.
+    pub fn publish(&self, clocks: Vec<ClockStatus>, events: &[(usize, &'static str)]) {
+        let Ok(mut published) = self.shared.published.lock() else {
+            return;
+        };
+        if published.clocks == clocks && events.is_empty() {
+            return;
+        }
+        published.revision += 1;
+        let revision = published.revision;
+        for (clock, event) in events {
+            let data = serde_json::json!({ "clock": clock }).to_string();
+            published.events.push_back((revision, event, data));
+        }
+        while published.events.len() > MAX_EVENTS {
+            published.events.pop_front();
+        }
+        published.clocks = clocks;
+        self.shared.changed.notify_all();
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+    host: Option<String>,
+    origin: Option<String>,
+}
+
+fn read_request(client: &TcpStream) -> io::Result<Request> {
+    client.set_read_timeout(Some(http::HEAD_TIMEOUT))?;
+    let mut reader = BufReader::new(client);
+    let head = http::read_head(&mut reader)?;
+    let mut parts = head.start_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let token = match head.header("authorization") {
+        Some(authorization) => authorization.strip_prefix("Bearer ").map(str::to_string),
+        None => query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+            .map(str::to_string),
+    };
+    let length = head
+        .header("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    // No endpoint takes a body, but closing the connection with it unread could lose the
+    // response.
+    io::copy(&mut reader.take(length.min(MAX_BODY)), &mut io::sink())?;
+    client.set_read_timeout(None)?;
+    Ok(Request {
+        method,
+        path: path.trim_end_matches('/').to_string(),
+        token,
+        host: head.header("host").map(str::to_string),
+        origin: head.header("origin").map(str::to_string),
+    })
+}
+
+/// Whether `host`, a `Host` header, names `address` itself or `localhost` with its port.
+fn own_host(host: &str, address: SocketAddr) -> bool {
+    host.parse::<SocketAddr>().is_ok_and(|host| host == address)
+        || host
+            .strip_prefix("localhost:")
+            .is_some_and(|port| port.parse() == Ok(address.port()))
+}
+
+/// The headers letting the allowed `origin` of a request read the response.
+fn cors_headers(origin: Option<&str>) -> String {
+    origin
+        .map(|origin| format!("Access-Control-Allow-Origin: {origin}\r\nVary: Origin\r\n"))
+        .unwrap_or_default()
+}
+
+fn respond(
+    client: &mut TcpStream,
+    status: &str,
+    cors: &str,
+    body: &serde_json::Value,
+) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        client,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         {cors}Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn error(client: &mut TcpStream, status: &str, cors: &str, message: &str) -> io::Result<()> {
+    respond(
+        client,
+        status,
+        cors,
+        &serde_json::json!({ "error": message }),
+    )
+}
+
+fn serve(
+    mut client: TcpStream,
+    shared: &Shared,
+    commands: &UnboundedSender<Command>,
+    access: &Access,
+) -> io::Result<()> {
+    let request = match read_request(&client) {
+        Ok(request) => request,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return error(&mut client, "400 Bad Request", "", &e.to_string());
+        }
+        Err(e) => return Err(e),
+    };
+    if !request
+        .host
+        .as_deref()
+        .is_some_and(|host| own_host(host, access.address))
+    {
+        return error(&mut client, "403 Forbidden", "", "wrong host");
+    }
+    if let Some(origin) = &request.origin {
+        if !access.origins.contains(origin) {
+            return error(&mut client, "403 Forbidden", "", "origin not allowed");
+        }
+    }
+    let cors = &cors_headers(request.origin.as_deref());
+    if access
+        .token
+        .as_ref()
+        .is_some_and(|token| request.token.as_ref() != Some(token))
+    {
+        return error(
+            &mut client,
+            "401 Unauthorized",
+            cors,
+            "missing or wrong token",
+        );
+    }
+    let segments = request.path.split('/').skip(1).collect::<Vec<_>>();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["state"]) => {
+            let clocks = shared
+                .published
+                .lock()
+                .map(|published| published.clocks.clone())
+                .unwrap_or_default();
+            respond(
+                &mut client,
+                "200 OK",
+                cors,
+                &serde_json::json!({ "clocks": clocks }),
+            )
+        }
+        ("GET", ["history"]) => respond(
+            &mut client,
+            "200 OK",
+            cors,
+            &serde_json::json!({ "sessions": history::sessions() }),
+        ),
+        ("GET", ["events"]) => stream_events(client, shared, cors),
+        ("POST", [action]) | ("POST", ["clocks", _, action]) => {
+            let clock = match segments.as_slice() {
+                [_, index, _] => match index.parse() {
+                    Ok(index) => index,
+                    Err(_) => return error(&mut client, "404 Not Found", cors, "no such clock"),
+                },
+                _ => 0,
+            };
+            let exists = shared
+                .published
+                .lock()
+                .is_ok_and(|published| clock < published.clocks.len());
+            if !exists {
+                return error(&mut client, "404 Not Found", cors, "no such clock");
+            }
+            let Some(action) = Action::parse(action) else {
+                return error(&mut client, "404 Not Found", cors, "no such action");
+            };
+            let _ = commands.unbounded_send(Command { clock, action });
+            respond(&mut client, "202 Accepted", cors, &serde_json::json!({}))
+        }
+        ("GET" | "POST", _) => error(&mut client, "404 Not Found", cors, "no such endpoint"),
+        _ => error(
+            &mut client,
+            "405 Method Not Allowed",
+            cors,
+            "use GET or POST",
+        ),
+    }
+}
+
+/// Sends the state, then every change until the client goes away.
+fn stream_events(mut client: TcpStream, shared: &Shared, cors: &str) -> io::Result<()> {
+    write!(
+        client,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+         {cors}Connection: close\r\n\r\n",
+    )?;
+    let mut sent = None;
+    loop {
+        let (revision, clocks, events) = {
+            let published = shared.published.lock().map_err(|_| io::ErrorKind::Other)?;
+            let (published, _) = shared
+                .changed
+                .wait_timeout_while(published, KEEP_ALIVE, |published| {
+                    sent == Some(published.revision)
+                })
+                .map_err(|_| io::ErrorKind::Other)?;
+            if sent == Some(published.revision) {
+                drop(published);
+                client.write_all(b": keep-alive\n\n")?;
+                continue;
+            }
+            let events = published
+                .events
+                .iter()
+                .filter(|(revision, ..)| sent.is_some_and(|sent| *revision > sent))
+                .cloned()
+                .collect::<Vec<_>>();
+            (published.revision, published.clocks.clone(), events)
+        };
+        let mut message = String::new();
+        for (_, name, data) in events {
+            message.push_str(&format!("event: {name}\ndata: {data}\n\n"));
+        }
+        let state = serde_json::json!({ "clocks": clocks });
+        message.push_str(&format!("event: state\ndata: {state}\n\n"));
+        client.write_all(message.as_bytes())?;
+        sent = Some(revision);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends `request` to a server with one clock and returns the response.
+    fn send(request: &str, token: Option<&str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let access = Access {
+            address,
+            token: token.map(str::to_string),
+            origins: vec!["http://localhost:8080".to_string()],
+        };
+        let request = request.replace("{address}", &address.to_string());
+        let server = std::thread::spawn(move || {
+            let shared = Shared::default();
+            shared.published.lock().unwrap().clocks.push(ClockStatus {
+                index: 0,
+                label: None,
+                mode: "pomodoro",
+                phase: "focus",
+                running: false,
+                display: "25:00".to_string(),
+                planned_secs: Some(1500),
+                elapsed_secs: 0,
+                remaining_secs: Some(1500),
+                task: None,
+            });
+            let (sender, _receiver) = mpsc::unbounded();
+            let (client, _) = listener.accept().unwrap();
+            let _ = serve(client, &shared, &sender, &access);
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+        response
+    }
+
+    #[test]
+    fn accepts_its_own_host_names() {
+        let address: SocketAddr = "127.0.0.1:7245".parse().unwrap();
+        assert!(own_host("127.0.0.1:7245", address));
+        assert!(own_host("localhost:7245", address));
+        assert!(!own_host("localhost:7246", address));
+        assert!(!own_host("127.0.0.1", address));
+        assert!(!own_host("evil.example:7245", address));
+        assert!(own_host("[::1]:7245", "[::1]:7245".parse().unwrap()));
+    }
+
+    #[test]
+    fn refuses_other_hosts() {
+        let response = send("GET /state HTTP/1.1\r\nHost: {address}\r\n\r\n", None);
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"display\":\"25:00\""));
+        let response = send("GET /state HTTP/1.1\r\nHost: rebound.example\r\n\r\n", None);
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+        let response = send("GET /state HTTP/1.1\r\n\r\n", None);
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+    }
+
+    #[test]
+    fn refuses_heads_too_large() {
+        let request = format!(
+            "GET /state HTTP/1.1\r\nHost: {{address}}\r\nX: {}\r\n\r\n",
+            "a".repeat(10_000)
+        );
+        assert!(send(&request, None).starts_with("HTTP/1.1 400 Bad Request"));
+    }
+
+    #[test]
+    fn refuses_origins_not_allowed() {
+        let response = send(
+            "POST /reset HTTP/1.1\r\nHost: {address}\r\nOrigin: https://evil.example\r\n\r\n",
+            None,
+        );
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+        let response = send(
+            "POST /reset HTTP/1.1\r\nHost: {address}\r\nOrigin: http://localhost:8080\r\n\r\n",
+            None,
+        );
+        assert!(response.starts_with("HTTP/1.1 202 Accepted"));
+        assert!(response.contains("Access-Control-Allow-Origin: http://localhost:8080\r\n"));
+    }
+
+    #[test]
+    fn needs_the_token_if_set() {
+        let request = "GET /state HTTP/1.1\r\nHost: {address}\r\n\r\n";
+        assert!(send(request, Some("secret")).starts_with("HTTP/1.1 401 Unauthorized"));
+        let request = "GET /state?token=secret HTTP/1.1\r\nHost: {address}\r\n\r\n";
+        assert!(send(request, Some("secret")).starts_with("HTTP/1.1 200 OK"));
+        let request =
+            "GET /state HTTP/1.1\r\nHost: {address}\r\nAuthorization: Bearer secret\r\n\r\n";
+        assert!(send(request, Some("secret")).starts_with("HTTP/1.1 200 OK"));
+    }
+}
@@ -0,0 +1,85 @@
+//! The desktop light/dark preference, read from the freedesktop settings portal
+//! (`org.freedesktop.appearance` `color-scheme`) and followed while the program runs.
+
+use iced::futures::Stream;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+/// Emits the current color scheme and every change to it. Emits nothing if the portal is not
+/// available.
+pub fn watch() -> impl Stream<Item = ColorScheme> {
+    iced::stream::channel(1, |output| async move {
+        #[cfg(target_os = "linux")]
+        if let Err(e) = portal::watch(output).await {
+            eprintln!("Failed to read the desktop color scheme: {e}");
+        }
+        #[cfg(not(target_os = "linux"))]
+        drop(output);
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod portal {
+    use super::ColorScheme;
+    use iced::futures::{channel::mpsc, SinkExt, StreamExt};
+    use zbus::zvariant::{OwnedValue, Value};
+
+    const NAMESPACE: &str = "org.freedesktop.appearance";
+    const KEY: &str = "color-scheme";
+
+    /// The portal reports 1 for dark, 2 for light and 0 for no preference, which is shown light.
+    fn scheme(value: &Value) -> ColorScheme {
+        match value {
+            Value::U32(1) => ColorScheme::Dark,
+            // The deprecated `Read` method wraps the setting in another variant.
+            Value::Value(inner) => scheme(inner),
+            _ => ColorScheme::Light,
+        }
+    }
+
+    pub async fn watch(mut output: mpsc::Sender<ColorScheme>) -> zbus::Result<()> {
+        let connection = zbus::Connection::session().await?;
+        let proxy = zbus::Proxy::new(
+            &connection,
+            "org.freedesktop.portal.Desktop",
+            "/org/freedesktop/portal/desktop",
+            "org.freedesktop.portal.Settings",
+        )
+        .await?;
+        // Subscribe first so that no change is missed between reading and listening.
+        let mut changes = proxy.receive_signal("SettingChanged").await?;
+        let current: OwnedValue = match proxy.call("ReadOne", &(NAMESPACE, KEY)).await {
+            Ok(value) => value,
+            Err(_) => proxy.call("Read", &(NAMESPACE, KEY)).await?,
+        };
+        if output.send(scheme(&current)).await.is_err() {
+            return Ok(());
+        }
+        while let Some(message) = changes.next().await {
+            let (namespace, key, value): (String, String, OwnedValue) =
+                message.body().deserialize()?;
+            if namespace == NAMESPACE && key == KEY && output.send(scheme(&value)).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn reads_the_portal_values() {
+            assert_eq!(scheme(&Value::U32(1)), ColorScheme::Dark);
+            assert_eq!(scheme(&Value::U32(2)), ColorScheme::Light);
+            assert_eq!(scheme(&Value::U32(0)), ColorScheme::Light);
+            let wrapped = Value::Value(Box::new(Value::U32(1)));
+            assert_eq!(scheme(&wrapped), ColorScheme::Dark);
+        }
+    }
+}
@@ -0,0 +1,427 @@
+//! A local HTTP proxy that refuses distracting sites while a clock is focusing. Browsers set
+//! to use it reach every other site as usual, and all sites again during breaks.
+//!
+//! Plain HTTP requests and HTTPS `CONNECT` tunnels are both filtered by host name; the
+//! proxy never looks inside the encrypted traffic. Connections to the sites that are still open
+//! when blocking starts are cut, so that tunnels opened during a break do not outlast it.
+
+use crate::http;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{self, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8118";
+const BLOCKED_PAGE: &str = "<!DOCTYPE html><title>Blocked</title>\
+    <p>This site is blocked while you focus. It is back on your next break.</p>";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct BlockerConfig {
+    /// Host names to block, each with its subdomains, e.g. `"reddit.com"`.
+    pub sites: Vec<String>,
+    /// The loopback address the proxy listens on, `127.0.0.1:8118` if unset.
+    pub listen: Option<String>,
+}
+
+pub struct Blocker {
+    proxy: Arc<Proxy>,
+}
+
+/// What the proxy's connections share.
+struct Proxy {
+    sites: Vec<String>,
+    /// Held while a connection is checked and registered, so that none slips past
+    /// [`Proxy::set`].
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    active: bool,
+    next_id: u64,
+    /// The connections being piped, with the host each goes to.
+    open: HashMap<u64, (String, TcpStream, TcpStream)>,
+}
+
+/// A connection in [`State::open`], removed again when dropped.
+struct Registration<'a> {
+    proxy: &'a Proxy,
+    id: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.proxy.state.lock() {
+            state.open.remove(&self.id);
+        }
+    }
+}
+
+impl Proxy {
+    fn new(sites: &[String]) -> Self {
+        Self {
+            sites: sites
+                .iter()
+                .map(|site| site.trim().trim_end_matches('.').to_lowercase())
+                .collect(),
+            state: Mutex::default(),
+        }
+    }
+
+    fn refuses(&self, host: &str) -> bool {
+        self.state.lock().is_ok_and(|state| state.active) && blocked(host, &self.sites)
+    }
+
+    /// Cuts the open connections to the sites when blocking starts.
+    fn set(&self, active: bool) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let starts = active && !state.active;
+        state.active = active;
+        if starts {
+            for (host, client, upstream) in state.open.values() {
+                if blocked(host, &self.sites) {
+                    let _ = client.shutdown(Shutdown::Both);
+                    let _ = upstream.shutdown(Shutdown::Both);
+                }
+            }
+        }
+    }
+
+    /// Adds a connection to `host` to the open ones, or returns `None` if `host` is blocked.
+    fn register(
+        &self,
+        host: &str,
+        client: &TcpStream,
+        upstream: &TcpStream,
+    ) -> io::Result<Option<Registration<'_>>> {
+        let mut state = self.state.lock().map_err(|_| io::ErrorKind::Other)?;
+        if state.active && blocked(host, &self.sites) {
+            return Ok(None);
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.open.insert(
+            id,
+            (host.to_string(), client.try_clone()?, upstream.try_clone()?),
+        );
+        Ok(Some(Registration { proxy: self, id }))
+    }
+}
+
+impl Blocker {
+    /// Starts the proxy, which lets everything through until [`Blocker::set`] turns it on. The
+    /// proxy does not start on an address other computers could reach.
+    pub fn new(config: &BlockerConfig) -> Self {
+        let proxy = Arc::new(Proxy::new(&config.sites));
+        let listen = config.listen.as_deref().unwrap_or(DEFAULT_LISTEN);
+        let address = match http::loopback_address(listen) {
+            Ok(address) => address,
+            Err(e) => {
+                eprintln!("Failed to start the blocking proxy: {e}");
+                return Self { proxy };
+            }
+        };
+        match TcpListener::bind(address) {
+            Ok(listener) => {
+                let proxy = proxy.clone();
+                std::thread::spawn(move || {
+                    for client in listener.incoming().flatten() {
+                        let proxy = proxy.clone();
+                        std::thread::spawn(move || {
+                            if let Err(e) = serve(client, &proxy) {
+                                // Clients hang up all the time; only say what else went wrong.
+                                if e.kind() != io::ErrorKind::BrokenPipe {
+                                    eprintln!("Blocking proxy: {e}");
+                                }
+                            }
+                        });
+                    }
+                });
+            }
+            Err(e) => eprintln!("Failed to start the blocking proxy on {address}: {e}"),
+        }
+        Self { proxy }
+    }
+
+    /// Blocks the sites while focusing.
+    pub fn set(&self, active: bool) {
+        self.proxy.set(active);
+    }
+}
+
+/// Whether `host` is one of `sites` or a subdomain of one.
+fn blocked(host: &str, sites: &[String]) -> bool {
+    let host = host.trim_end_matches('.').to_lowercase();
+    sites.iter().any(|site| {
+        host == *site
+            || host
+                .strip_suffix(site.as_str())
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+/// Splits `host:port`, also with a bracketed IPv6 host.
+fn split_port(authority: &str, default_port: u16) -> (&str, u16) {
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse().ok()),
+        _ => (authority, None),
+    };
+    (
+        host.trim_start_matches('[').trim_end_matches(']'),
+        port.unwrap_or(default_port),
+    )
+}
+
+fn respond(client: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        client,
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn serve(mut client: TcpStream, proxy: &Proxy) -> io::Result<()> {
+    client.set_read_timeout(Some(http::HEAD_TIMEOUT))?;
+    let mut reader = BufReader::new(client.try_clone()?);
+    let head = match http::read_head(&mut reader) {
+        Ok(head) => head,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return respond(&mut client, "400 Bad Request", &e.to_string());
+        }
+        Err(e) => return Err(e),
+    };
+    client.set_read_timeout(None)?;
+    let mut parts = head.start_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return respond(&mut client, "400 Bad Request", "");
+    };
+
+    let (authority, default_port, path) = if method.eq_ignore_ascii_case("CONNECT") {
+        (target, 443, None)
+    } else if let Some(rest) = target.strip_prefix("http://") {
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        (
+            authority,
+            80,
+            Some(if path.is_empty() { "/" } else { path }),
+        )
+    } else {
+        return respond(
+            &mut client,
+            "400 Bad Request",
+            "This is a proxy; set it as your browser's HTTP proxy.",
+        );
+    };
+    let (host, port) = split_port(authority, default_port);
+    if proxy.refuses(host) {
+        return respond(&mut client, "403 Forbidden", BLOCKED_PAGE);
+    }
+    let mut upstream = match TcpStream::connect((host, port)) {
+        Ok(upstream) => upstream,
+        Err(e) => return respond(&mut client, "502 Bad Gateway", &e.to_string()),
+    };
+    // Blocking may have started while connecting.
+    let Some(_registration) = proxy.register(host, &client, &upstream)? else {
+        return respond(&mut client, "403 Forbidden", BLOCKED_PAGE);
+    };
+    match path {
+        None => client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")?,
+        Some(path) => {
+            // One request per connection, so that every request passes the filter.
+            let mut forwarded = format!("{method} {path} {version}\r\n");
+            for (name, value) in &head.headers {
+                if !["connection", "proxy-connection", "keep-alive"]
+                    .iter()
+                    .any(|hop| name.eq_ignore_ascii_case(hop))
+                {
+                    forwarded.push_str(&format!("{name}: {value}\r\n"));
+                }
+            }
+            forwarded.push_str("Connection: close\r\n\r\n");
+            upstream.write_all(forwarded.as_bytes())?;
+        }
+    }
+    pipe(reader, client, upstream)
+}
+
+/// Copies both ways until the site is done. `from_client` may hold what the client sent after
+/// the request head.
+fn pipe(
+    mut from_client: BufReader<TcpStream>,
+    mut client: TcpStream,
+    upstream: TcpStream,
+) -> io::Result<()> {
+    let mut to_upstream = upstream.try_clone()?;
+    let uploads = std::thread::spawn(move || {
+        let _ = io::copy(&mut from_client, &mut to_upstream);
+        let _ = to_upstream.shutdown(Shutdown::Write);
+    });
+    let mut from_upstream = upstream;
+    let result = io::copy(&mut from_upstream, &mut client);
+    // Nothing the client still sends has anywhere to go.
+    let _ = client.shutdown(Shutdown::Both);
+    let _ = uploads.join();
+    result.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, Read},
+        net::SocketAddr,
+        sync::mpsc,
+        time::Duration,
+    };
+
+    fn sites() -> Vec<String> {
+        vec!["reddit.com".to_string(), "127.0.0.2".to_string()]
+    }
+
+    #[test]
+    fn blocks_sites_and_their_subdomains() {
+        assert!(blocked("reddit.com", &sites()));
+        assert!(blocked("old.Reddit.com.", &sites()));
+        assert!(!blocked("notreddit.com", &sites()));
+        assert!(!blocked("reddit.com.example", &sites()));
+    }
+
+    #[test]
+    fn splits_the_port_off() {
+        assert_eq!(split_port("example.com:8080", 80), ("example.com", 8080));
+        assert_eq!(split_port("example.com", 443), ("example.com", 443));
+        assert_eq!(split_port("[::1]:8443", 443), ("::1", 8443));
+        assert_eq!(split_port("[::1]", 80), ("::1", 80));
+    }
+
+    /// Sends `request` through the proxy and returns the response.
+    fn proxy(request: &str, active: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (client, _) = listener.accept().unwrap();
+            let proxy = Proxy::new(&sites());
+            proxy.set(active);
+            let _ = serve(client, &proxy);
+        });
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+        response
+    }
+
+    /// A site answering one request with `ok`, which passes on the request head it got.
+    fn site() -> (SocketAddr, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (heads, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut upstream, _) = listener.accept().unwrap();
+            let mut head = String::new();
+            for line in BufReader::new(upstream.try_clone().unwrap()).lines() {
+                let line = line.unwrap();
+                if line.is_empty() {
+                    break;
+                }
+                head.push_str(&line);
+                head.push('\n');
+            }
+            let _ = heads.send(head);
+            upstream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .unwrap();
+        });
+        (address, received)
+    }
+
+    #[test]
+    fn refuses_blocked_sites_while_active() {
+        let request = "GET http://www.reddit.com/r/rust HTTP/1.1\r\nHost: www.reddit.com\r\n\r\n";
+        assert!(proxy(request, true).starts_with("HTTP/1.1 403 Forbidden"));
+        let request = "CONNECT 127.0.0.2:443 HTTP/1.1\r\nHost: 127.0.0.2:443\r\n\r\n";
+        assert!(proxy(request, true).starts_with("HTTP/1.1 403 Forbidden"));
+    }
+
+    #[test]
+    fn forwards_other_sites_one_request_per_connection() {
+        let (site, heads) = site();
+        let response = proxy(
+            &format!(
+                "GET http://{site}/page?q=1 HTTP/1.1\r\nHost: {site}\r\n\
+                 Proxy-Connection: keep-alive\r\nConnection: keep-alive\r\n\r\n"
+            ),
+            true,
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\nok"));
+        assert_eq!(
+            heads.recv().unwrap(),
+            format!("GET /page?q=1 HTTP/1.1\nHost: {site}\nConnection: close\n")
+        );
+    }
+
+    #[test]
+    fn tunnels_to_sites_while_inactive() {
+        let (site, _) = site();
+        let response = proxy(
+            &format!("CONNECT {site} HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n"),
+            false,
+        );
+        assert!(response.starts_with("HTTP/1.1 200 Connection established\r\n\r\nHTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn cuts_tunnels_to_sites_when_blocking_starts() {
+        let site = TcpListener::bind("127.0.0.1:0").unwrap();
+        let site_address = site.local_addr().unwrap();
+        // Holds the tunnel open until the proxy cuts it.
+        let held = std::thread::spawn(move || {
+            let (mut upstream, _) = site.accept().unwrap();
+            io::copy(&mut upstream, &mut io::sink()).unwrap();
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let proxy = Arc::new(Proxy::new(&["127.0.0.1".to_string()]));
+        let server = {
+            let proxy = proxy.clone();
+            std::thread::spawn(move || {
+                let (client, _) = listener.accept().unwrap();
+                let _ = serve(client, &proxy);
+            })
+        };
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client
+            .write_all(format!("CONNECT {site_address} HTTP/1.1\r\n\r\n").as_bytes())
+            .unwrap();
+        let established = b"HTTP/1.1 200 Connection established\r\n\r\n";
+        let mut response = vec![0; established.len()];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(response, established);
+
+        proxy.set(true);
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+        server.join().unwrap();
+        held.join().unwrap();
+        assert!(proxy.state.lock().unwrap().open.is_empty());
+    }
+
+    #[test]
+    fn answers_requests_not_meant_for_a_proxy() {
+        assert!(proxy("GET / HTTP/1.1\r\n\r\n", true).starts_with("HTTP/1.1 400 Bad Request"));
+        assert!(proxy("nonsense\r\n\r\n", true).starts_with("HTTP/1.1 400 Bad Request"));
+    }
+}
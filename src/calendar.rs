@@ -0,0 +1,635 @@
+//! Meetings from local iCalendar (`.ics`) files, so that pomodoros make way for them. The files
+//! are only read from disk; nothing is fetched over the network.
+//!
+//! Daily and weekly repeating events are expanded, other repeat rules only count their first
+//! occurrence. All-day, cancelled and free (`TRANSP:TRANSPARENT`) events are ignored.
+
+use chrono::{
+    DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc, Weekday,
+};
+use ical::{parser::ical::component::IcalEvent, property::Property};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// How far ahead repeating events are expanded; the files are read again every day.
+const LOOKAHEAD_DAYS: u64 = 8;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CalendarConfig {
+    /// An `.ics` file, or a directory whose `.ics` files are all read.
+    pub path: String,
+    /// Pauses a running pomodoro while an event is on and resumes it afterwards; on if unset.
+    pub hold_during_events: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub summary: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+}
+
+/// What a clock shows about the calendar above the time.
+#[derive(Debug, Clone)]
+pub enum Notice {
+    /// The pomodoro would run into this event.
+    Ahead(Event),
+    /// The pomodoro is held while this event is on.
+    Held(Event),
+}
+
+impl Notice {
+    pub fn text(&self) -> String {
+        const MAX_CHARS: usize = 14;
+        let (summary, prefix, time) = match self {
+            Notice::Ahead(event) => (&event.summary, "", event.start.format(" %H:%M · f fit")),
+            Notice::Held(event) => (&event.summary, "held: ", event.end.format(" until %H:%M")),
+        };
+        let summary = if summary.chars().count() > MAX_CHARS {
+            format!(
+                "{}…",
+                summary.chars().take(MAX_CHARS - 1).collect::<String>()
+            )
+        } else {
+            summary.clone()
+        };
+        format!("{prefix}{summary}{time}")
+    }
+}
+
+pub struct Calendar {
+    path: PathBuf,
+    /// The files read last time with their modification times, to notice changes.
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+    loaded_on: Option<NaiveDate>,
+    events: Vec<Event>,
+}
+
+impl Calendar {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            stamps: Vec::new(),
+            loaded_on: None,
+            events: Vec::new(),
+        }
+    }
+
+    /// Reads the files again if they changed or a new day started.
+    pub fn refresh(&mut self) {
+        let stamps = files(&self.path)
+            .into_iter()
+            .map(|file| {
+                let modified = std::fs::metadata(&file).and_then(|m| m.modified()).ok();
+                (file, modified)
+            })
+            .collect::<Vec<_>>();
+        let now = Local::now();
+        if stamps == self.stamps && self.loaded_on == Some(now.date_naive()) {
+            return;
+        }
+        self.events = stamps
+            .iter()
+            .flat_map(|(file, _)| read(file, now))
+            .collect();
+        self.events.sort_by_key(|event| event.start);
+        self.stamps = stamps;
+        self.loaded_on = Some(now.date_naive());
+    }
+
+    /// The event going on at `now`, the one ending last if several overlap.
+    pub fn current(&self, now: DateTime<Local>) -> Option<&Event> {
+        self.events
+            .iter()
+            .filter(|event| event.start <= now && now < event.end)
+            .max_by_key(|event| event.end)
+    }
+
+    /// The next event starting after `now`.
+    pub fn next(&self, now: DateTime<Local>) -> Option<&Event> {
+        self.events.iter().find(|event| event.start > now)
+    }
+}
+
+fn files(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    let mut files = std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("ics"))
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// The events of `file` that are on within a day before and [`LOOKAHEAD_DAYS`] after `now`.
+fn read(file: &Path, now: DateTime<Local>) -> Vec<Event> {
+    let reader = match std::fs::File::open(file) {
+        Ok(reader) => BufReader::new(reader),
+        Err(e) => {
+            eprintln!("Failed to open calendar {}: {e}", file.display());
+            return Vec::new();
+        }
+    };
+    let from = now - TimeDelta::days(1);
+    let to = now + TimeDelta::days(LOOKAHEAD_DAYS as i64);
+    let mut events = Vec::new();
+    let mut moved = Vec::new();
+    for calendar in ical::IcalParser::new(reader) {
+        let calendar = match calendar {
+            Ok(calendar) => calendar,
+            Err(e) => {
+                eprintln!("Failed to read calendar {}: {e}", file.display());
+                continue;
+            }
+        };
+        for event in &calendar.events {
+            let uid = value(event, "UID").unwrap_or_default().to_string();
+            // A changed occurrence of a repeating event replaces the one the rule produces.
+            let replaces = property(event, "RECURRENCE-ID")
+                .and_then(date_time)
+                .and_then(|(time, zone)| zone.resolve(time));
+            if let Some(time) = replaces {
+                moved.push((uid.clone(), time));
+            }
+            if let Some(occurrences) = occurrences(event, to) {
+                events.extend(
+                    occurrences
+                        .into_iter()
+                        .filter(|occurrence| occurrence.end > from && occurrence.start < to)
+                        .map(|occurrence| (uid.clone(), replaces.is_some(), occurrence)),
+                );
+            }
+        }
+    }
+    events
+        .into_iter()
+        .filter(|(uid, replacement, event)| {
+            *replacement || !moved.contains(&(uid.clone(), event.start))
+        })
+        .map(|(_, _, event)| event)
+        .collect()
+}
+
+fn property<'e>(event: &'e IcalEvent, name: &str) -> Option<&'e Property> {
+    event
+        .properties
+        .iter()
+        .find(|property| property.name == name)
+}
+
+fn value<'e>(event: &'e IcalEvent, name: &str) -> Option<&'e str> {
+    property(event, name)?.value.as_deref()
+}
+
+fn parameter<'p>(property: &'p Property, name: &str) -> Option<&'p str> {
+    property
+        .params
+        .as_ref()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .and_then(|(_, values)| values.first())
+        .map(String::as_str)
+}
+
+/// The time zone a date-time is written in.
+#[derive(Clone, Copy)]
+enum Zone {
+    Utc,
+    Named(chrono_tz::Tz),
+    /// No time zone given, or one that is not in the time zone database: the local time.
+    Floating,
+}
+
+/// The moment the wall clock of `zone` shows `time`. A time skipped by a daylight saving change
+/// is moved on by an hour, and a repeated one counts from its first occurrence.
+pub fn resolve_time<Z: TimeZone>(zone: &Z, time: NaiveDateTime) -> Option<DateTime<Local>> {
+    zone.from_local_datetime(&time)
+        .earliest()
+        .or_else(|| {
+            zone.from_local_datetime(&(time + TimeDelta::hours(1)))
+                .earliest()
+        })
+        .map(|time| time.with_timezone(&Local))
+}
+
+impl Zone {
+    /// The moment `time` in this zone.
+    fn resolve(self, time: NaiveDateTime) -> Option<DateTime<Local>> {
+        match self {
+            Zone::Utc => Some(Utc.from_utc_datetime(&time).with_timezone(&Local)),
+            Zone::Named(zone) => resolve_time(&zone, time),
+            Zone::Floating => resolve_time(&Local, time),
+        }
+    }
+}
+
+fn parse_date_time(value: &str, zone: Zone) -> Option<(NaiveDateTime, Zone)> {
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(value) => (value, Zone::Utc),
+        None => (value, zone),
+    };
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((time, zone))
+}
+
+fn zone(property: &Property) -> Zone {
+    parameter(property, "TZID")
+        .and_then(|zone| zone.parse().ok())
+        .map_or(Zone::Floating, Zone::Named)
+}
+
+/// A date-time property with its time zone, or `None` for dates of all-day events.
+fn date_time(property: &Property) -> Option<(NaiveDateTime, Zone)> {
+    if parameter(property, "VALUE").is_some_and(|value| value.eq_ignore_ascii_case("DATE")) {
+        return None;
+    }
+    parse_date_time(property.value.as_deref()?, zone(property))
+}
+
+/// An iCalendar duration such as `PT1H30M` or `P1D`.
+fn duration(value: &str) -> Option<TimeDelta> {
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut seconds = 0;
+    let mut number = 0;
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number = number * 10 + i64::from(c.to_digit(10)?),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                seconds += number
+                    * match c {
+                        'W' => 7 * 24 * 3600,
+                        'D' => 24 * 3600,
+                        'H' => 3600,
+                        'M' => 60,
+                        _ => 1,
+                    };
+                number = 0;
+            }
+            _ => return None,
+        }
+    }
+    Some(TimeDelta::seconds(sign * seconds))
+}
+
+enum Frequency {
+    Daily,
+    Weekly,
+}
+
+/// The supported part of an `RRULE`.
+struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<DateTime<Local>>,
+    weekdays: Vec<Weekday>,
+}
+
+fn rule(value: &str) -> Option<Rule> {
+    let mut rule = Rule {
+        frequency: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        weekdays: Vec::new(),
+    };
+    let mut frequency = None;
+    for part in value.split(';') {
+        let (key, value) = part.split_once('=')?;
+        match key {
+            "FREQ" => {
+                frequency = match value {
+                    "DAILY" => Some(Frequency::Daily),
+                    "WEEKLY" => Some(Frequency::Weekly),
+                    _ => None,
+                }
+            }
+            "INTERVAL" => rule.interval = value.parse().ok().filter(|interval| *interval > 0)?,
+            "COUNT" => rule.count = value.parse().ok(),
+            "UNTIL" => {
+                rule.until = match NaiveDate::parse_from_str(value, "%Y%m%d") {
+                    Ok(date) => Zone::Floating.resolve(date.and_hms_opt(23, 59, 59)?),
+                    Err(_) => parse_date_time(value, Zone::Floating)
+                        .and_then(|(time, zone)| zone.resolve(time)),
+                }
+            }
+            "BYDAY" => {
+                rule.weekdays = value
+                    .split(',')
+                    // Skip the position, as in `1MO`, which only monthly rules use.
+                    .filter_map(|day| day.get(day.len().checked_sub(2)?..))
+                    .filter_map(weekday)
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+    rule.frequency = frequency?;
+    Some(rule)
+}
+
+fn weekday(day: &str) -> Option<Weekday> {
+    Some(match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// The start times a rule produces from `start`, up to `to`.
+fn repeat(
+    start: NaiveDateTime,
+    zone: Zone,
+    rule: &Rule,
+    to: DateTime<Local>,
+) -> Vec<NaiveDateTime> {
+    let mut weekdays = match rule.frequency {
+        Frequency::Daily => Vec::new(),
+        Frequency::Weekly if rule.weekdays.is_empty() => vec![start.weekday()],
+        Frequency::Weekly => rule.weekdays.clone(),
+    };
+    weekdays.sort_by_key(|day| day.num_days_from_monday());
+    let period = match rule.frequency {
+        Frequency::Daily => u64::from(rule.interval),
+        Frequency::Weekly => 7 * u64::from(rule.interval),
+    };
+    let first = match rule.frequency {
+        Frequency::Daily => start.date(),
+        Frequency::Weekly => start
+            .date()
+            .checked_sub_days(Days::new(start.weekday().num_days_from_monday().into()))
+            .unwrap_or(start.date()),
+    };
+    let mut times = Vec::new();
+    for period_index in 0.. {
+        let Some(base) = first.checked_add_days(Days::new(period * period_index)) else {
+            break;
+        };
+        let days = if weekdays.is_empty() {
+            vec![base]
+        } else {
+            weekdays
+                .iter()
+                .filter_map(|day| {
+                    base.checked_add_days(Days::new(day.num_days_from_monday().into()))
+                })
+                .collect()
+        };
+        for day in days {
+            let time = day.and_time(start.time());
+            if time < start {
+                continue;
+            }
+            let resolved = zone.resolve(time);
+            if resolved.is_none_or(|resolved| resolved > to)
+                || rule
+                    .count
+                    .is_some_and(|count| times.len() >= count as usize)
+                || rule
+                    .until
+                    .zip(resolved)
+                    .is_some_and(|(until, resolved)| resolved > until)
+            {
+                return times;
+            }
+            times.push(time);
+        }
+    }
+    times
+}
+
+/// Every occurrence of `event` starting up to `to`, or `None` if it is not a timed event.
+fn occurrences(event: &IcalEvent, to: DateTime<Local>) -> Option<Vec<Event>> {
+    if value(event, "STATUS").is_some_and(|status| status.eq_ignore_ascii_case("CANCELLED"))
+        || value(event, "TRANSP").is_some_and(|transp| transp.eq_ignore_ascii_case("TRANSPARENT"))
+    {
+        return None;
+    }
+    let (start, zone) = date_time(property(event, "DTSTART")?)?;
+    let length = match property(event, "DTEND").and_then(date_time) {
+        Some((end, end_zone)) => end_zone.resolve(end)? - zone.resolve(start)?,
+        None => value(event, "DURATION")
+            .and_then(duration)
+            .unwrap_or_default(),
+    };
+    let summary = value(event, "SUMMARY")
+        .map(|summary| {
+            summary
+                .replace("\\n", " ")
+                .replace("\\N", " ")
+                .replace("\\,", ",")
+                .replace("\\;", ";")
+                .replace("\\\\", "\\")
+        })
+        .unwrap_or_else(|| "event".to_string());
+    let excluded = event
+        .properties
+        .iter()
+        .filter(|property| property.name == "EXDATE")
+        .flat_map(|property| {
+            let zone = self::zone(property);
+            property
+                .value
+                .iter()
+                .flat_map(|value| value.split(','))
+                .filter_map(move |value| parse_date_time(value, zone))
+        })
+        .filter_map(|(time, zone)| zone.resolve(time))
+        .collect::<Vec<_>>();
+    let starts = match value(event, "RRULE").and_then(rule) {
+        Some(rule) => repeat(start, zone, &rule, to),
+        None => vec![start],
+    };
+    Some(
+        starts
+            .into_iter()
+            .filter_map(|start| zone.resolve(start))
+            .filter(|start| !excluded.contains(start))
+            .map(|start| Event {
+                summary: summary.clone(),
+                start,
+                end: start + length.max(TimeDelta::zero()),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(date: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn at(date: &str) -> DateTime<Local> {
+        Utc.from_utc_datetime(&utc(date)).with_timezone(&Local)
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(duration("PT1H30M"), Some(TimeDelta::minutes(90)));
+        assert_eq!(
+            duration("P1DT15S"),
+            Some(TimeDelta::seconds(24 * 3600 + 15))
+        );
+        assert_eq!(duration("+P2W"), Some(TimeDelta::weeks(2)));
+        assert_eq!(duration("-PT15M"), Some(TimeDelta::minutes(-15)));
+        assert_eq!(duration("1H"), None);
+        assert_eq!(duration("PT1X"), None);
+    }
+
+    #[test]
+    fn parses_daily_and_weekly_rules_only() {
+        let rule = rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,1WE;COUNT=4;WKST=MO").unwrap();
+        assert!(matches!(rule.frequency, Frequency::Weekly));
+        assert_eq!(rule.interval, 2);
+        assert_eq!(rule.count, Some(4));
+        assert_eq!(rule.weekdays, [Weekday::Mon, Weekday::Wed]);
+        assert_eq!(
+            rule_until("FREQ=DAILY;UNTIL=20260310T120000Z"),
+            Some(at("2026-03-10 12:00"))
+        );
+        assert!(rule_until("FREQ=DAILY;UNTIL=20260310").is_some());
+        assert!(super::rule("FREQ=MONTHLY").is_none());
+        assert!(super::rule("FREQ=DAILY;INTERVAL=0").is_none());
+        assert!(super::rule("INTERVAL=2").is_none());
+    }
+
+    fn rule_until(value: &str) -> Option<DateTime<Local>> {
+        super::rule(value)?.until
+    }
+
+    #[test]
+    fn repeats_on_the_rule_days() {
+        let starts = |value: &str, start: &str| {
+            repeat(
+                utc(start),
+                Zone::Utc,
+                &rule(value).unwrap(),
+                at("2026-04-01 00:00"),
+            )
+        };
+        // Every other week on Mondays and Wednesdays, starting on a Wednesday.
+        assert_eq!(
+            starts("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE", "2026-03-04 09:00"),
+            [
+                utc("2026-03-04 09:00"),
+                utc("2026-03-16 09:00"),
+                utc("2026-03-18 09:00"),
+                utc("2026-03-30 09:00"),
+            ]
+        );
+        assert_eq!(
+            starts("FREQ=DAILY;INTERVAL=3;COUNT=3", "2026-03-10 09:00"),
+            [
+                utc("2026-03-10 09:00"),
+                utc("2026-03-13 09:00"),
+                utc("2026-03-16 09:00"),
+            ]
+        );
+        // The range ends before the count is reached.
+        assert_eq!(
+            starts("FREQ=DAILY;COUNT=5", "2026-03-30 09:00"),
+            [utc("2026-03-30 09:00"), utc("2026-03-31 09:00")]
+        );
+        assert_eq!(
+            starts("FREQ=DAILY;UNTIL=20260312T090000Z", "2026-03-10 09:00"),
+            [
+                utc("2026-03-10 09:00"),
+                utc("2026-03-11 09:00"),
+                utc("2026-03-12 09:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_times_skipped_by_daylight_saving() {
+        let berlin: chrono_tz::Tz = "Europe/Berlin".parse().unwrap();
+        // 02:30 does not exist on the day clocks go forward, and 02:30 is in CEST an hour later.
+        assert_eq!(
+            resolve_time(&berlin, utc("2026-03-29 02:30")),
+            Some(at("2026-03-29 01:30"))
+        );
+        // 02:30 happens twice on the day clocks go back; the first one is in CEST.
+        assert_eq!(
+            resolve_time(&berlin, utc("2026-10-25 02:30")),
+            Some(at("2026-10-25 00:30"))
+        );
+    }
+
+    #[test]
+    fn reads_moved_and_excluded_occurrences() {
+        let calendar = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:standup\r
+DTSTART:20260302T090000Z\r
+DURATION:PT15M\r
+RRULE:FREQ=DAILY;COUNT=5\r
+EXDATE:20260304T090000Z\r
+SUMMARY:Standup\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+RECURRENCE-ID:20260303T090000Z\r
+DTSTART:20260303T100000Z\r
+DTEND:20260303T101500Z\r
+SUMMARY:Standup\\, moved\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:holiday\r
+DTSTART;VALUE=DATE:20260302\r
+SUMMARY:Holiday\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:cancelled\r
+STATUS:CANCELLED\r
+DTSTART:20260302T130000Z\r
+DTEND:20260302T140000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        let path = std::env::temp_dir().join(format!("tomato-calendar-{}.ics", std::process::id()));
+        std::fs::write(&path, calendar).unwrap();
+        let mut events = read(&path, at("2026-03-02 08:00"));
+        std::fs::remove_file(&path).unwrap();
+        events.sort_by_key(|event| event.start);
+        let events = events
+            .iter()
+            .map(|event| (event.summary.as_str(), event.start, event.end - event.start))
+            .collect::<Vec<_>>();
+        let quarter = TimeDelta::minutes(15);
+        assert_eq!(
+            events,
+            [
+                ("Standup", at("2026-03-02 09:00"), quarter),
+                ("Standup, moved", at("2026-03-03 10:00"), quarter),
+                ("Standup", at("2026-03-05 09:00"), quarter),
+                ("Standup", at("2026-03-06 09:00"), quarter),
+            ]
+        );
+    }
+}
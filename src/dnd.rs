@@ -0,0 +1,449 @@
+//! Desktop Do-Not-Disturb while a clock is focusing, on GNOME, KDE Plasma, mako and dunst.
+//!
+//! The notification server is asked from a thread of its own. Whatever was changed is written
+//! to a file until it is undone, so a run that crashed is undone on the next start.
+
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::mpsc, time::Duration};
+
+const STATE_PATH: &str = "tomato_dnd.toml";
+/// How long quitting waits for the setting to be restored. What is not restored by then is
+/// restored on the next start.
+const RESTORE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Asks the notification server which one it is.
+    Auto,
+    Gnome,
+    Kde,
+    Mako,
+    Dunst,
+}
+
+/// A Do-Not-Disturb this program turned on, to be turned off again.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+struct Enabled {
+    backend: Backend,
+    /// The KDE inhibition, which ends by itself with the program's bus connection.
+    cookie: Option<u32>,
+}
+
+pub struct DoNotDisturb {
+    on: bool,
+    commands: Option<mpsc::Sender<bool>>,
+    /// Disconnected once the worker thread is done.
+    finished: Option<mpsc::Receiver<()>>,
+}
+
+impl DoNotDisturb {
+    /// Also undoes the Do-Not-Disturb left on by a run that crashed.
+    pub fn new(backend: Backend) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        #[cfg(target_os = "linux")]
+        let finished = {
+            let (done, finished) = mpsc::channel::<()>();
+            std::thread::spawn(move || {
+                let _done = done;
+                match zbus::blocking::Connection::session() {
+                    Ok(session) => {
+                        linux::control(&session, backend, receiver, Path::new(STATE_PATH))
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to connect to the session bus for Do-Not-Disturb: {e}")
+                    }
+                }
+            });
+            Some(finished)
+        };
+        #[cfg(not(target_os = "linux"))]
+        let finished = {
+            let _ = (backend, receiver);
+            None
+        };
+        Self {
+            on: false,
+            commands: Some(commands),
+            finished,
+        }
+    }
+
+    /// Turns Do-Not-Disturb on while focusing and restores the previous setting otherwise.
+    pub fn set(&mut self, on: bool) {
+        if on == self.on {
+            return;
+        }
+        self.on = on;
+        if let Some(commands) = &self.commands {
+            let _ = commands.send(on);
+        }
+    }
+
+    /// Restores the previous setting and waits until it is restored, for a few seconds at
+    /// most. Nothing is changed afterwards.
+    pub fn restore(&mut self) {
+        self.commands = None;
+        if let Some(finished) = self.finished.take() {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(RESTORE_TIMEOUT) {
+                eprintln!(
+                    "Do-Not-Disturb is restored on the next start, restoring it took too long"
+                );
+            }
+        }
+    }
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn load(path: &Path) -> Option<Enabled> {
+    let saved = std::fs::read_to_string(path).ok()?;
+    toml::from_str(&saved)
+        .inspect_err(|e| eprintln!("Failed to read {}: {e}", path.display()))
+        .ok()
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn save(path: &Path, enabled: Option<Enabled>) {
+    let result = match enabled {
+        Some(enabled) => std::fs::write(path, toml::to_string(&enabled).unwrap()),
+        None => std::fs::remove_file(path).or_else(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        }),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to write {}: {e}", path.display());
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{Backend, Enabled};
+    use std::{collections::HashMap, path::Path, process::Command, sync::mpsc};
+    use zbus::{
+        blocking::{proxy::Builder, Connection, Proxy},
+        proxy::CacheProperties,
+        zvariant::Value,
+    };
+
+    const DESTINATION: &str = "org.freedesktop.Notifications";
+    const PATH: &str = "/org/freedesktop/Notifications";
+    const MAKO_PATH: &str = "/fr/emersion/Mako";
+    /// The mako mode hiding notifications, which the mako config has to define.
+    const MAKO_MODE: &str = "do-not-disturb";
+    const GNOME_SCHEMA: &str = "org.gnome.desktop.notifications";
+    const GNOME_KEY: &str = "show-banners";
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    /// Carries out the commands until the sender is dropped, then restores the setting. What
+    /// is turned on is recorded at `state_path` until it is turned off.
+    pub fn control(
+        session: &Connection,
+        backend: Backend,
+        commands: mpsc::Receiver<bool>,
+        state_path: &Path,
+    ) {
+        if let Some(left_on) = super::load(state_path) {
+            // A KDE inhibition ended with the run that crashed, and its cookie may have been
+            // handed out again since.
+            let restored = match left_on.backend {
+                Backend::Kde => Ok(()),
+                _ => disable(session, left_on),
+            };
+            match restored {
+                Ok(()) => super::save(state_path, None),
+                Err(e) => eprintln!("Failed to restore Do-Not-Disturb: {e}"),
+            }
+        }
+        let mut resolved = None;
+        let mut enabled = None;
+        for on in commands {
+            match (on, enabled) {
+                (true, None) => {
+                    let backend = match resolved {
+                        Some(backend) => backend,
+                        None => match resolve(session, backend) {
+                            Ok(backend) => *resolved.insert(backend),
+                            Err(e) => {
+                                eprintln!("Failed to find the notification server: {e}");
+                                continue;
+                            }
+                        },
+                    };
+                    match enable(session, backend) {
+                        Ok(turned_on) => {
+                            enabled = turned_on;
+                            super::save(state_path, enabled);
+                        }
+                        Err(e) => eprintln!("Failed to turn on Do-Not-Disturb: {e}"),
+                    }
+                }
+                (false, Some(turned_on)) => {
+                    enabled = None;
+                    match disable(session, turned_on) {
+                        Ok(()) => super::save(state_path, None),
+                        Err(e) => eprintln!("Failed to restore Do-Not-Disturb: {e}"),
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(turned_on) = enabled {
+            match disable(session, turned_on) {
+                Ok(()) => super::save(state_path, None),
+                Err(e) => eprintln!("Failed to restore Do-Not-Disturb: {e}"),
+            }
+        }
+    }
+
+    fn proxy<'a>(session: &Connection, path: &'a str, interface: &'a str) -> Result<Proxy<'a>> {
+        Ok(Builder::new(session)
+            .destination(DESTINATION)?
+            .path(path)?
+            .interface(interface)?
+            .cache_properties(CacheProperties::No)
+            .build()?)
+    }
+
+    fn resolve(session: &Connection, backend: Backend) -> Result<Backend> {
+        if backend != Backend::Auto {
+            return Ok(backend);
+        }
+        let (name, ..): (String, String, String, String) =
+            proxy(session, PATH, "org.freedesktop.Notifications")?
+                .call("GetServerInformation", &())?;
+        let lowercase = name.to_lowercase();
+        [
+            ("gnome", Backend::Gnome),
+            ("plasma", Backend::Kde),
+            ("kde", Backend::Kde),
+            ("mako", Backend::Mako),
+            ("dunst", Backend::Dunst),
+        ]
+        .into_iter()
+        .find(|(known, _)| lowercase.contains(known))
+        .map(|(_, backend)| backend)
+        .ok_or_else(|| format!("{name} has no Do-Not-Disturb setting the clock knows").into())
+    }
+
+    fn gsettings(args: &[&str]) -> Result<String> {
+        let output = Command::new("gsettings").args(args).output()?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr)
+                .trim()
+                .to_string()
+                .into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn mako_modes(session: &Connection) -> Result<Vec<String>> {
+        Ok(proxy(session, MAKO_PATH, "fr.emersion.Mako")?.call("ListModes", &())?)
+    }
+
+    fn set_mako_modes(session: &Connection, modes: Vec<String>) -> Result<()> {
+        Ok(proxy(session, MAKO_PATH, "fr.emersion.Mako")?.call("SetModes", &(modes,))?)
+    }
+
+    fn dunst(session: &Connection) -> Result<Proxy<'static>> {
+        proxy(session, PATH, "org.dunstproject.cmd0")
+    }
+
+    /// Turns Do-Not-Disturb on, or returns `None` if it already was.
+    fn enable(session: &Connection, backend: Backend) -> Result<Option<Enabled>> {
+        let turned_on = Enabled {
+            backend,
+            cookie: None,
+        };
+        match backend {
+            Backend::Auto => unreachable!("resolved before"),
+            Backend::Gnome => {
+                if gsettings(&["get", GNOME_SCHEMA, GNOME_KEY])? == "false" {
+                    return Ok(None);
+                }
+                gsettings(&["set", GNOME_SCHEMA, GNOME_KEY, "false"])?;
+            }
+            Backend::Kde => {
+                // Inhibitions add up, so one the user has stays in place.
+                let hints: HashMap<&str, Value> = HashMap::new();
+                let cookie: u32 = proxy(session, PATH, "org.freedesktop.Notifications")?
+                    .call("Inhibit", &("tomato-clock", "Focusing", hints))?;
+                return Ok(Some(Enabled {
+                    cookie: Some(cookie),
+                    ..turned_on
+                }));
+            }
+            Backend::Mako => {
+                let mut modes = mako_modes(session)?;
+                if modes.iter().any(|mode| mode == MAKO_MODE) {
+                    return Ok(None);
+                }
+                modes.push(MAKO_MODE.to_string());
+                set_mako_modes(session, modes)?;
+            }
+            Backend::Dunst => {
+                let dunst = dunst(session)?;
+                if dunst.get_property::<bool>("paused")? {
+                    return Ok(None);
+                }
+                dunst.set_property("paused", true)?;
+            }
+        }
+        Ok(Some(turned_on))
+    }
+
+    fn disable(session: &Connection, turned_on: Enabled) -> Result<()> {
+        match turned_on.backend {
+            Backend::Auto => {}
+            Backend::Gnome => {
+                gsettings(&["set", GNOME_SCHEMA, GNOME_KEY, "true"])?;
+            }
+            Backend::Kde => {
+                if let Some(cookie) = turned_on.cookie {
+                    proxy(session, PATH, "org.freedesktop.Notifications")?
+                        .call_method("UnInhibit", &(cookie,))?;
+                }
+            }
+            Backend::Mako => {
+                let modes = mako_modes(session)?
+                    .into_iter()
+                    .filter(|mode| mode != MAKO_MODE)
+                    .collect();
+                set_mako_modes(session, modes)?;
+            }
+            Backend::Dunst => dunst(session)?.set_property("paused", false)?,
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::test_bus::Bus;
+        use std::{
+            path::PathBuf,
+            sync::{Arc, Mutex},
+        };
+        use zbus::blocking::connection;
+
+        struct Dunst {
+            paused: Arc<Mutex<bool>>,
+        }
+
+        #[zbus::interface(name = "org.dunstproject.cmd0")]
+        impl Dunst {
+            #[zbus(property, name = "paused")]
+            fn paused(&self) -> bool {
+                *self.paused.lock().unwrap()
+            }
+
+            #[zbus(property, name = "paused")]
+            fn set_paused(&mut self, paused: bool) {
+                *self.paused.lock().unwrap() = paused;
+            }
+        }
+
+        /// Serves a dunst on the bus, with its pause setting shared with the test.
+        fn serve(bus: &Bus, paused: bool) -> (Connection, Arc<Mutex<bool>>) {
+            let paused = Arc::new(Mutex::new(paused));
+            let dunst = Dunst {
+                paused: paused.clone(),
+            };
+            let connection = connection::Builder::address(bus.address.as_str())
+                .unwrap()
+                .name(DESTINATION)
+                .unwrap()
+                .serve_at(PATH, dunst)
+                .unwrap()
+                .build()
+                .unwrap();
+            (connection, paused)
+        }
+
+        fn state_path(name: &str) -> PathBuf {
+            std::env::temp_dir().join(format!("tomato-dnd-{}-{name}.toml", std::process::id()))
+        }
+
+        #[test]
+        fn undoes_what_a_crashed_run_left_on() {
+            let Some(bus) = Bus::start() else {
+                return;
+            };
+            let (_dunst, paused) = serve(&bus, true);
+            let path = state_path("crashed");
+            let left_on = Enabled {
+                backend: Backend::Dunst,
+                cookie: None,
+            };
+            super::super::save(&path, Some(left_on));
+            let (_, commands) = mpsc::channel();
+            control(&bus.connect(), Backend::Auto, commands, &path);
+            assert!(!*paused.lock().unwrap());
+            assert!(!path.exists());
+        }
+
+        #[test]
+        fn turns_on_and_restores_only_its_own_setting() {
+            let Some(bus) = Bus::start() else {
+                return;
+            };
+            let (_dunst, paused) = serve(&bus, false);
+            let path = state_path("toggled");
+            let session = bus.connect();
+            let (sender, commands) = mpsc::channel();
+            let worker = {
+                let path = path.clone();
+                std::thread::spawn(move || control(&session, Backend::Dunst, commands, &path))
+            };
+            sender.send(true).unwrap();
+            // The setting is recorded once it is changed.
+            for _ in 0..500 {
+                if path.exists() {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            assert!(*paused.lock().unwrap());
+            sender.send(false).unwrap();
+            drop(sender);
+            worker.join().unwrap();
+            assert!(!*paused.lock().unwrap());
+            assert!(!path.exists());
+
+            // A Do-Not-Disturb the user turned on is left on.
+            let (sender, commands) = mpsc::channel();
+            *paused.lock().unwrap() = true;
+            sender.send(true).unwrap();
+            sender.send(false).unwrap();
+            drop(sender);
+            control(&bus.connect(), Backend::Dunst, commands, &path);
+            assert!(*paused.lock().unwrap());
+            assert!(!path.exists());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_what_it_turned_on_until_it_is_undone() {
+        let path = std::env::temp_dir().join(format!("tomato-dnd-{}.toml", std::process::id()));
+        assert_eq!(load(&path), None);
+        let enabled = Enabled {
+            backend: Backend::Kde,
+            cookie: Some(7),
+        };
+        save(&path, Some(enabled));
+        assert_eq!(load(&path), Some(enabled));
+        save(&path, None);
+        assert!(!path.exists());
+        save(&path, None);
+
+        std::fs::write(&path, "backend = \"plasma\"").unwrap();
+        assert_eq!(load(&path), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
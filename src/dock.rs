@@ -0,0 +1,221 @@
+//! Keeps a window docked to an edge or a corner of its monitor.
+
+use crate::monitors::Monitor;
+use serde::{Deserialize, Serialize};
+
+/// The edge or corner a window is docked to.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Start,
+    End,
+}
+
+impl Anchor {
+    fn sides(self) -> (Option<Side>, Option<Side>) {
+        match self {
+            Anchor::TopLeft => (Some(Side::Start), Some(Side::Start)),
+            Anchor::Top => (None, Some(Side::Start)),
+            Anchor::TopRight => (Some(Side::End), Some(Side::Start)),
+            Anchor::Left => (Some(Side::Start), None),
+            Anchor::Right => (Some(Side::End), None),
+            Anchor::BottomLeft => (Some(Side::Start), Some(Side::End)),
+            Anchor::Bottom => (None, Some(Side::End)),
+            Anchor::BottomRight => (Some(Side::End), Some(Side::End)),
+        }
+    }
+
+    fn from_sides(horizontal: Option<Side>, vertical: Option<Side>) -> Option<Self> {
+        Some(match (horizontal, vertical) {
+            (Some(Side::Start), Some(Side::Start)) => Anchor::TopLeft,
+            (None, Some(Side::Start)) => Anchor::Top,
+            (Some(Side::End), Some(Side::Start)) => Anchor::TopRight,
+            (Some(Side::Start), None) => Anchor::Left,
+            (Some(Side::End), None) => Anchor::Right,
+            (Some(Side::Start), Some(Side::End)) => Anchor::BottomLeft,
+            (None, Some(Side::End)) => Anchor::Bottom,
+            (Some(Side::End), Some(Side::End)) => Anchor::BottomRight,
+            (None, None) => return None,
+        })
+    }
+
+    /// Moves towards `direction` (one of the four edges) and snaps the other axis to the
+    /// nearest side, so repeated presses walk the window around the corners.
+    pub fn towards(
+        direction: Anchor,
+        current: Option<Anchor>,
+        monitor: &Monitor,
+        position: iced::Point,
+        size: iced::Size,
+    ) -> Anchor {
+        let nearest = |offset: f32, length: f32, window: f32| {
+            if offset + window / 2.0 < length / 2.0 {
+                Side::Start
+            } else {
+                Side::End
+            }
+        };
+        let (mut horizontal, mut vertical) = current.map_or((None, None), Anchor::sides);
+        let horizontal_side = horizontal.unwrap_or_else(|| {
+            nearest(
+                position.x - monitor.position.x,
+                monitor.size.width,
+                size.width,
+            )
+        });
+        let vertical_side = vertical.unwrap_or_else(|| {
+            nearest(
+                position.y - monitor.position.y,
+                monitor.size.height,
+                size.height,
+            )
+        });
+        match direction.sides() {
+            (Some(side), _) => {
+                horizontal = Some(side);
+                vertical = Some(vertical_side);
+            }
+            (_, Some(side)) => {
+                vertical = Some(side);
+                horizontal = Some(horizontal_side);
+            }
+            (None, None) => {}
+        }
+        Anchor::from_sides(horizontal, vertical).unwrap_or(direction)
+    }
+}
+
+fn axis(side: Option<Side>, start: f32, length: f32, window: f32, offset: f32, margin: f32) -> f32 {
+    match side {
+        Some(Side::Start) => start + margin,
+        Some(Side::End) => start + length - window - margin,
+        None => start + offset.clamp(0.0, (length - window).max(0.0)),
+    }
+}
+
+/// The position of a window of `size` docked at `anchor`. On an edge, the coordinate along the
+/// edge comes from `relative`, the saved position relative to the monitor.
+pub fn anchored_position(
+    monitor: &Monitor,
+    size: iced::Size,
+    anchor: Anchor,
+    margin: f32,
+    relative: [f32; 2],
+) -> iced::Point {
+    let (horizontal, vertical) = anchor.sides();
+    iced::Point::new(
+        axis(
+            horizontal,
+            monitor.position.x,
+            monitor.size.width,
+            size.width,
+            relative[0],
+            margin,
+        ),
+        axis(
+            vertical,
+            monitor.position.y,
+            monitor.size.height,
+            size.height,
+            relative[1],
+            margin,
+        ),
+    )
+}
+
+/// The anchor for a window dropped at `position`, if it is within `distance` of an edge.
+pub fn snap(
+    monitor: &Monitor,
+    position: iced::Point,
+    size: iced::Size,
+    distance: f32,
+) -> Option<Anchor> {
+    let side = |offset: f32, length: f32, window: f32| {
+        if offset <= distance {
+            Some(Side::Start)
+        } else if length - (offset + window) <= distance {
+            Some(Side::End)
+        } else {
+            None
+        }
+    };
+    Anchor::from_sides(
+        side(
+            position.x - monitor.position.x,
+            monitor.size.width,
+            size.width,
+        ),
+        side(
+            position.y - monitor.position.y,
+            monitor.size.height,
+            size.height,
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> Monitor {
+        Monitor {
+            name: "DP-1".to_string(),
+            primary: true,
+            position: iced::Point::new(1920.0, 0.0),
+            size: iced::Size::new(1920.0, 1080.0),
+        }
+    }
+
+    fn snapped(x: f32, y: f32) -> Option<Anchor> {
+        snap(
+            &monitor(),
+            iced::Point::new(1920.0 + x, y),
+            iced::Size::new(150.0, 45.0),
+            24.0,
+        )
+    }
+
+    #[test]
+    fn snaps_to_edges_and_corners_within_the_distance() {
+        assert_eq!(snapped(10.0, 500.0), Some(Anchor::Left));
+        assert_eq!(snapped(1920.0 - 150.0 - 24.0, 500.0), Some(Anchor::Right));
+        assert_eq!(snapped(800.0, 0.0), Some(Anchor::Top));
+        assert_eq!(snapped(800.0, 1080.0 - 45.0 - 5.0), Some(Anchor::Bottom));
+        assert_eq!(snapped(0.0, 0.0), Some(Anchor::TopLeft));
+        assert_eq!(
+            snapped(1920.0 - 150.0, 1080.0 - 45.0),
+            Some(Anchor::BottomRight)
+        );
+    }
+
+    #[test]
+    fn leaves_windows_away_from_the_edges() {
+        assert_eq!(snapped(25.0, 500.0), None);
+        assert_eq!(snapped(800.0, 500.0), None);
+    }
+
+    #[test]
+    fn anchored_position_keeps_the_margin_and_the_offset_along_the_edge() {
+        let size = iced::Size::new(150.0, 45.0);
+        assert_eq!(
+            anchored_position(&monitor(), size, Anchor::BottomRight, 8.0, [0.0, 0.0]),
+            iced::Point::new(1920.0 + 1920.0 - 150.0 - 8.0, 1080.0 - 45.0 - 8.0)
+        );
+        assert_eq!(
+            anchored_position(&monitor(), size, Anchor::Top, 8.0, [300.0, 500.0]),
+            iced::Point::new(1920.0 + 300.0, 8.0)
+        );
+    }
+}
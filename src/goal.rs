@@ -0,0 +1,199 @@
+//! A daily focus goal, counted from the session history, and the streak of days it was met.
+
+use crate::history::{self, DayTotal};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const DEFAULT_TEXT: &str = "Daily goal reached! Streak: {streak}";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressStyle {
+    #[default]
+    Dots,
+    Fraction,
+}
+
+/// The goal is met once every target that is set is reached.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GoalConfig {
+    /// Pomodoros to complete every day.
+    pub pomodoros: Option<u32>,
+    /// Minutes to focus every day, on any clock.
+    pub minutes: Option<u64>,
+    /// How the progress is shown on the main clock.
+    pub progress: Option<ProgressStyle>,
+    /// The reminder text when the goal is reached, with `{streak}` for the streak in days.
+    pub text: Option<String>,
+}
+
+pub struct Goal {
+    config: GoalConfig,
+    /// The history revision and the day the totals were read at.
+    read: Option<(u64, NaiveDate)>,
+    today: DayTotal,
+    streak: u32,
+}
+
+impl Goal {
+    pub fn new(config: GoalConfig) -> Self {
+        Self {
+            config,
+            read: None,
+            today: DayTotal::default(),
+            streak: 0,
+        }
+    }
+
+    fn met(&self, total: &DayTotal) -> bool {
+        (self.config.pomodoros.is_some() || self.config.minutes.is_some())
+            && self
+                .config
+                .pomodoros
+                .is_none_or(|pomodoros| total.completed >= pomodoros)
+            && self
+                .config
+                .minutes
+                .is_none_or(|minutes| total.focused_secs >= minutes * 60)
+    }
+
+    /// Reads the history again if it was written to or a new day started. Returns whether
+    /// today's goal has just been reached.
+    pub fn refresh(&mut self) -> bool {
+        let key = (history::revision(), crate::today());
+        if self.read == Some(key) {
+            return false;
+        }
+        self.take_totals(key, &history::daily_totals())
+    }
+
+    /// Takes in the totals read at `key`, the history revision and today's date. Returns whether
+    /// today's goal has just been reached.
+    fn take_totals(
+        &mut self,
+        key: (u64, NaiveDate),
+        totals: &BTreeMap<NaiveDate, DayTotal>,
+    ) -> bool {
+        let was_met = self.read.is_some_and(|(_, day)| day == key.1) && self.met(&self.today);
+        let first = self.read.is_none();
+        self.today = totals.get(&key.1).copied().unwrap_or_default();
+        self.read = Some(key);
+
+        // Today only breaks the streak once it is over.
+        let mut day = Some(key.1);
+        if !self.met(&self.today) {
+            day = key.1.pred_opt();
+        }
+        self.streak = 0;
+        while let Some(met) = day.filter(|day| totals.get(day).is_some_and(|total| self.met(total)))
+        {
+            self.streak += 1;
+            day = met.pred_opt();
+        }
+        !first && !was_met && self.met(&self.today)
+    }
+
+    /// Today's progress, e.g. `●●●○○` or `3/5`, followed by the streak.
+    pub fn progress(&self) -> String {
+        const MAX_DOTS: u64 = 10;
+        let (done, target, unit) = match (self.config.pomodoros, self.config.minutes) {
+            (Some(pomodoros), _) => (u64::from(self.today.completed), u64::from(pomodoros), ""),
+            (None, Some(minutes)) => (self.today.focused_secs / 60, minutes, " min"),
+            (None, None) => return String::new(),
+        };
+        let progress = match self.config.progress.unwrap_or_default() {
+            ProgressStyle::Dots => {
+                let dots = match unit {
+                    "" => target.clamp(1, MAX_DOTS),
+                    _ => 5,
+                };
+                let filled = (done * dots / target.max(1)).min(dots) as usize;
+                format!(
+                    "{}{}",
+                    "●".repeat(filled),
+                    "○".repeat(dots as usize - filled)
+                )
+            }
+            ProgressStyle::Fraction => format!("{done}/{target}{unit}"),
+        };
+        match self.streak {
+            0 => progress,
+            streak => format!("{progress} · {streak}d"),
+        }
+    }
+
+    pub fn celebration(&self) -> String {
+        self.config
+            .text
+            .as_deref()
+            .unwrap_or(DEFAULT_TEXT)
+            .replace("{streak}", &self.streak.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn goal() -> Goal {
+        Goal::new(GoalConfig {
+            pomodoros: Some(4),
+            minutes: None,
+            progress: Some(ProgressStyle::Fraction),
+            text: None,
+        })
+    }
+
+    fn totals(days: &[(NaiveDate, u32)]) -> BTreeMap<NaiveDate, DayTotal> {
+        days.iter()
+            .map(|&(day, completed)| {
+                (
+                    day,
+                    DayTotal {
+                        completed,
+                        focused_secs: u64::from(completed) * 25 * 60,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap()
+    }
+
+    #[test]
+    fn counts_the_streak_up_to_yesterday_until_today_is_met() {
+        let mut goal = goal();
+        let history = [(day(1), 5), (day(3), 4), (day(4), 6), (day(5), 2)];
+        assert!(!goal.take_totals((1, day(5)), &totals(&history)));
+        assert_eq!(goal.progress(), "2/4 · 2d");
+
+        let history = [(day(1), 5), (day(3), 4), (day(4), 6), (day(5), 4)];
+        assert!(goal.take_totals((2, day(5)), &totals(&history)));
+        assert_eq!(goal.progress(), "4/4 · 3d");
+        assert_eq!(goal.celebration(), "Daily goal reached! Streak: 3");
+
+        // Reached already; a fifth pomodoro celebrates nothing.
+        let history = [(day(1), 5), (day(3), 4), (day(4), 6), (day(5), 5)];
+        assert!(!goal.take_totals((3, day(5)), &totals(&history)));
+    }
+
+    #[test]
+    fn a_missed_day_ends_the_streak() {
+        let mut goal = goal();
+        let history = [(day(3), 4), (day(4), 3)];
+        goal.take_totals((1, day(6)), &totals(&history));
+        assert_eq!(goal.progress(), "0/4");
+    }
+
+    #[test]
+    fn a_goal_met_before_starting_is_not_celebrated() {
+        let mut goal = goal();
+        assert!(!goal.take_totals((1, day(5)), &totals(&[(day(5), 4)])));
+        // Nor is a new day, which starts unmet.
+        assert!(!goal.take_totals((1, day(6)), &totals(&[(day(5), 4)])));
+        assert_eq!(goal.progress(), "0/4 · 1d");
+    }
+}
@@ -0,0 +1,162 @@
+use crate::Mode;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const HISTORY_PATH: &str = "tomato_history.toml";
+
+/// Counts the writes to the history file, see [`revision`].
+static REVISION: AtomicU64 = AtomicU64::new(0);
+
+/// A finished pomodoro or stopwatch run, appended to the history file as a `[[sessions]]` table.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SessionRecord {
+    pub label: Option<String>,
+    pub mode: Mode,
+    /// Unix timestamps in seconds.
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub planned_secs: Option<u64>,
+    pub focused_secs: u64,
+    /// Idle time the user chose not to count as focus.
+    #[serde(default)]
+    pub discarded_idle_secs: u64,
+    /// The stopwatch time at every lap, in milliseconds.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub laps_ms: Vec<u64>,
+    pub completed: bool,
+}
+
+/// A strict break the user skipped, kept to enforce the daily skip allowance.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SkippedBreak {
+    pub at: u64,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct History {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sessions: Vec<SessionRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    skipped_breaks: Vec<SkippedBreak>,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// The local calendar day of a timestamp from [`unix_now`].
+pub fn local_date(timestamp: u64) -> Option<chrono::NaiveDate> {
+    use chrono::TimeZone;
+    chrono::Local
+        .timestamp_opt(timestamp.try_into().ok()?, 0)
+        .earliest()
+        .map(|time| time.date_naive())
+}
+
+pub fn append(record: &SessionRecord) {
+    append_history(&History {
+        sessions: vec![record.clone()],
+        ..Default::default()
+    });
+}
+
+pub fn append_skipped_break() {
+    append_history(&History {
+        skipped_breaks: vec![SkippedBreak { at: unix_now() }],
+        ..Default::default()
+    });
+}
+
+pub fn skipped_breaks_on(day: chrono::NaiveDate) -> u32 {
+    load()
+        .skipped_breaks
+        .iter()
+        .filter(|skipped| local_date(skipped.at) == Some(day))
+        .count() as u32
+}
+
+/// The number of pomodoros completed on `day`.
+pub fn completed_on(day: chrono::NaiveDate) -> u32 {
+    load()
+        .sessions
+        .iter()
+        .filter(|session| {
+            session.completed
+                && matches!(session.mode, Mode::Pomodoro)
+                && local_date(session.ended_at) == Some(day)
+        })
+        .count() as u32
+}
+
+/// Every recorded session, oldest first.
+pub fn sessions() -> Vec<SessionRecord> {
+    load().sessions
+}
+
+/// What was done on one day.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DayTotal {
+    /// Completed pomodoros.
+    pub completed: u32,
+    /// Focus time on any clock.
+    pub focused_secs: u64,
+}
+
+/// The totals of every day in the history, by the day the sessions ended.
+pub fn daily_totals() -> BTreeMap<chrono::NaiveDate, DayTotal> {
+    let mut totals = BTreeMap::<_, DayTotal>::new();
+    for session in load().sessions {
+        let Some(day) = local_date(session.ended_at) else {
+            continue;
+        };
+        let total = totals.entry(day).or_default();
+        if session.completed && matches!(session.mode, Mode::Pomodoro) {
+            total.completed += 1;
+        }
+        total.focused_secs += session.focused_secs;
+    }
+    totals
+}
+
+/// Changes whenever the history file is written, so what was read from it can be kept until
+/// then.
+pub fn revision() -> u64 {
+    REVISION.load(Ordering::Relaxed)
+}
+
+fn load() -> History {
+    let Ok(toml_str) = std::fs::read_to_string(HISTORY_PATH) else {
+        return History::default();
+    };
+    toml::from_str(&toml_str).unwrap_or_else(|e| {
+        eprintln!("Failed to parse history file: {e}");
+        History::default()
+    })
+}
+
+/// Appends `history` to the history file. Every entry is a self-contained array-of-tables
+/// entry, so the file stays valid TOML without being rewritten.
+fn append_history(history: &History) {
+    let result = toml::to_string(history)
+        .map_err(|e| e.to_string())
+        .and_then(|toml_str| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(HISTORY_PATH)
+                .and_then(|mut file| writeln!(file, "{toml_str}"))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        eprintln!("Failed to write history file: {e}");
+    }
+    REVISION.fetch_add(1, Ordering::Relaxed);
+}
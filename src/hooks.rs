@@ -0,0 +1,240 @@
+//! Shell commands run when a clock starts, pauses, times out and so on, e.g. to set a chat
+//! status or pause music. Every command runs on its own thread and is killed once it runs
+//! longer than the timeout, so a slow command never holds up the clocks. On Unix the commands
+//! it started are killed with it.
+
+use iced::futures::channel::oneshot;
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct HooksConfig {
+    pub start: Option<String>,
+    pub pause: Option<String>,
+    pub resume: Option<String>,
+    pub reset: Option<String>,
+    pub timeout: Option<String>,
+    pub break_start: Option<String>,
+    pub break_end: Option<String>,
+    pub shutdown: Option<String>,
+    /// How long a command may run before it is killed, 10 seconds if unset.
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Start,
+    Pause,
+    Resume,
+    Reset,
+    TimeOut,
+    BreakStart,
+    BreakEnd,
+    Shutdown,
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Start => "start",
+            Event::Pause => "pause",
+            Event::Resume => "resume",
+            Event::Reset => "reset",
+            Event::TimeOut => "timeout",
+            Event::BreakStart => "break_start",
+            Event::BreakEnd => "break_end",
+            Event::Shutdown => "shutdown",
+        }
+    }
+}
+
+/// The clock an event happened on, passed to the command as `TOMATO_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct Details {
+    pub mode: &'static str,
+    pub phase: &'static str,
+    pub planned: Option<Duration>,
+    /// The time focused in the current session.
+    pub elapsed: Duration,
+    pub remaining: Option<Duration>,
+    pub task: Option<String>,
+}
+
+impl Details {
+    fn variables(&self, event: Event) -> Vec<(&'static str, String)> {
+        let mut variables = vec![
+            ("TOMATO_EVENT", event.name().to_string()),
+            ("TOMATO_MODE", self.mode.to_string()),
+            ("TOMATO_PHASE", self.phase.to_string()),
+            ("TOMATO_ELAPSED_SECS", self.elapsed.as_secs().to_string()),
+        ];
+        if let Some(planned) = self.planned {
+            variables.push(("TOMATO_PLANNED_SECS", planned.as_secs().to_string()));
+        }
+        if let Some(remaining) = self.remaining {
+            variables.push(("TOMATO_REMAINING_SECS", remaining.as_secs().to_string()));
+        }
+        if let Some(task) = &self.task {
+            variables.push(("TOMATO_TASK", task.clone()));
+        }
+        variables
+    }
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    }
+    #[cfg(not(windows))]
+    {
+        use std::os::unix::process::CommandExt;
+        let mut shell = Command::new("sh");
+        // A process group of its own, so that the commands it starts can be killed with it.
+        shell.args(["-c", command]).process_group(0);
+        shell
+    }
+}
+
+/// Kills the shell and, on Unix, everything it started.
+fn kill(shell: &mut std::process::Child) {
+    #[cfg(unix)]
+    // SAFETY: kill only sends a signal, here to the group the shell leads.
+    unsafe {
+        libc::kill(-(shell.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = shell.kill();
+}
+
+impl HooksConfig {
+    fn command(&self, event: Event) -> Option<&str> {
+        match event {
+            Event::Start => &self.start,
+            Event::Pause => &self.pause,
+            Event::Resume => &self.resume,
+            Event::Reset => &self.reset,
+            Event::TimeOut => &self.timeout,
+            Event::BreakStart => &self.break_start,
+            Event::BreakEnd => &self.break_end,
+            Event::Shutdown => &self.shutdown,
+        }
+        .as_deref()
+    }
+
+    /// Starts the command of the event, if one is set. The returned future completes once the
+    /// command has exited or was killed; dropping it leaves the command running.
+    pub fn run(&self, event: Event, details: &Details) -> Option<impl Future<Output = ()>> {
+        let command = self.command(event)?.to_string();
+        let name = event.name();
+        let variables = details.variables(event);
+        let timeout = Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+        let (sender, receiver) = oneshot::channel();
+        std::thread::spawn(move || {
+            let child = shell(&command).envs(variables).stdin(Stdio::null()).spawn();
+            let mut child = match child {
+                Ok(child) => child,
+                Err(e) => {
+                    eprintln!("Failed to run the {name} hook: {e}");
+                    let _ = sender.send(());
+                    return;
+                }
+            };
+            let deadline = Instant::now() + timeout;
+            loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => {
+                        if !status.success() {
+                            eprintln!("The {name} hook failed: {status}");
+                        }
+                        break;
+                    }
+                    Ok(None) if Instant::now() >= deadline => {
+                        kill(&mut child);
+                        let _ = child.wait();
+                        eprintln!(
+                            "The {name} hook took longer than {} s and was stopped",
+                            timeout.as_secs()
+                        );
+                        break;
+                    }
+                    Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+                    Err(e) => {
+                        eprintln!("Failed to wait for the {name} hook: {e}");
+                        break;
+                    }
+                }
+            }
+            let _ = sender.send(());
+        });
+        Some(async move {
+            let _ = receiver.await;
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn details() -> Details {
+        Details {
+            mode: "pomodoro",
+            phase: "focus",
+            planned: Some(Duration::from_secs(1500)),
+            elapsed: Duration::from_secs(60),
+            remaining: Some(Duration::from_secs(1440)),
+            task: Some("write the report".to_string()),
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tomato-hooks-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn passes_the_clock_in_the_environment() {
+        let output = temp_path("env");
+        let hooks = HooksConfig {
+            start: Some(format!("env > '{}'", output.display())),
+            ..HooksConfig::default()
+        };
+        assert!(hooks.run(Event::Pause, &details()).is_none());
+        iced::futures::executor::block_on(hooks.run(Event::Start, &details()).unwrap());
+        let env = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        for variable in [
+            "TOMATO_EVENT=start",
+            "TOMATO_MODE=pomodoro",
+            "TOMATO_PHASE=focus",
+            "TOMATO_PLANNED_SECS=1500",
+            "TOMATO_ELAPSED_SECS=60",
+            "TOMATO_REMAINING_SECS=1440",
+            "TOMATO_TASK=write the report",
+        ] {
+            assert!(env.lines().any(|line| line == variable), "{variable}");
+        }
+    }
+
+    #[test]
+    fn kills_slow_hooks_with_what_they_started() {
+        let marker = temp_path("marker");
+        let hooks = HooksConfig {
+            timeout: Some(format!("(sleep 2; touch '{}') & wait", marker.display())),
+            timeout_secs: Some(1),
+            ..HooksConfig::default()
+        };
+        let started = Instant::now();
+        iced::futures::executor::block_on(hooks.run(Event::TimeOut, &details()).unwrap());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        std::thread::sleep(Duration::from_millis(2500));
+        assert!(!marker.exists());
+    }
+}
@@ -0,0 +1,133 @@
+//! The small part of HTTP/1.1 the API, `tomato-clock status` and the blocking proxy share:
+//! reading a message head with limits on how long it may take and how large it may be.
+
+use std::{
+    io::{self, BufRead, Read},
+    net::SocketAddr,
+    time::Duration,
+};
+
+/// How long the other side may take to send a head.
+pub const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+/// The longest line of a head.
+const MAX_LINE: u64 = 8 * 1024;
+/// The most header fields of a head.
+const MAX_HEADERS: usize = 100;
+
+/// A request or response head.
+#[derive(Debug, Default)]
+pub struct Head {
+    /// The request line or the status line.
+    pub start_line: String,
+    /// The header fields in the order they came, as names and values.
+    pub headers: Vec<(String, String)>,
+}
+
+impl Head {
+    /// The value of the last header field called `name`.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .rev()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads a head up to the empty line ending it, or to the end of the stream. A line longer than
+/// [`MAX_LINE`] or more than [`MAX_HEADERS`] fields are an [`io::ErrorKind::InvalidData`]
+/// error. The caller sets [`HEAD_TIMEOUT`] on the stream.
+pub fn read_head(reader: &mut impl BufRead) -> io::Result<Head> {
+    let mut head = Head {
+        start_line: read_line(reader)?,
+        headers: Vec::new(),
+    };
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            return Ok(head);
+        }
+        if head.headers.len() == MAX_HEADERS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "too many header fields",
+            ));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            head.headers
+                .push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+}
+
+/// A line without its line ending.
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    reader.take(MAX_LINE).read_line(&mut line)?;
+    if line.len() as u64 == MAX_LINE && !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+    }
+    line.truncate(line.trim_end_matches(['\r', '\n']).len());
+    Ok(line)
+}
+
+/// The address in `listen`, if it is a loopback address.
+pub fn loopback_address(listen: &str) -> Result<SocketAddr, String> {
+    let address = listen
+        .parse::<SocketAddr>()
+        .map_err(|e| format!("invalid address {listen:?}: {e}"))?;
+    if !address.ip().is_loopback() {
+        return Err(format!("{address} is not a loopback address"));
+    }
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_start_line_and_the_fields() {
+        let mut reader = "GET /state HTTP/1.1\r\nHost: 127.0.0.1\r\nhost:  localhost \r\n\
+                          broken\r\n\r\nbody"
+            .as_bytes();
+        let head = read_head(&mut reader).unwrap();
+        assert_eq!(head.start_line, "GET /state HTTP/1.1");
+        assert_eq!(head.headers.len(), 2);
+        assert_eq!(head.header("HOST"), Some("localhost"));
+        assert_eq!(head.header("Origin"), None);
+        assert_eq!(reader, b"body");
+    }
+
+    #[test]
+    fn ends_with_the_stream() {
+        let head = read_head(&mut "HTTP/1.1 200 OK\r\nA: b".as_bytes()).unwrap();
+        assert_eq!(head.start_line, "HTTP/1.1 200 OK");
+        assert_eq!(head.header("a"), Some("b"));
+        assert!(read_head(&mut "".as_bytes()).unwrap().start_line.is_empty());
+    }
+
+    #[test]
+    fn refuses_heads_too_large() {
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE as usize));
+        let error = read_head(&mut long_line.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let many_fields = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "A: b\r\n".repeat(MAX_HEADERS + 1)
+        );
+        let error = read_head(&mut many_fields.as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let enough_fields = format!("GET / HTTP/1.1\r\n{}\r\n", "A: b\r\n".repeat(MAX_HEADERS));
+        assert!(read_head(&mut enough_fields.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn accepts_loopback_addresses_only() {
+        assert!(loopback_address("127.0.0.1:8118").is_ok());
+        assert!(loopback_address("[::1]:8118").is_ok());
+        assert!(loopback_address("0.0.0.0:8118").is_err());
+        assert!(loopback_address("192.168.1.2:8118").is_err());
+        assert!(loopback_address("localhost:8118").is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! How long the user has been away from the keyboard and mouse.
+//!
+//! On Linux the Wayland `ext-idle-notify-v1` protocol is tried first, then the X11 screensaver
+//! extension, then the logind `IdleHint`, which `loginctl` is asked for on a thread of its own.
+//! Other platforms never report idle time.
+
+use std::time::Duration;
+
+pub struct IdleMonitor {
+    threshold: Duration,
+    #[cfg(target_os = "linux")]
+    backend: linux::Backend,
+}
+
+impl IdleMonitor {
+    /// `threshold` is the idle time after which the user counts as away; Wayland only reports
+    /// idleness once it has been reached.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            #[cfg(target_os = "linux")]
+            backend: linux::Backend::new(threshold),
+        }
+    }
+
+    /// How long the user has been away, once it is at least the threshold.
+    pub fn away(&mut self) -> Option<Duration> {
+        away(self.idle_time(), self.threshold)
+    }
+
+    /// The time since the last user input, or `None` if it cannot be determined.
+    fn idle_time(&mut self) -> Option<Duration> {
+        #[cfg(target_os = "linux")]
+        {
+            self.backend.idle_time()
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+}
+
+fn away(idle: Option<Duration>, threshold: Duration) -> Option<Duration> {
+    idle.filter(|idle| *idle >= threshold)
+}
+
+/// The idle time in the output of `loginctl show-session -p IdleHint -p IdleSinceHint`, given
+/// the time since the Unix epoch.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn logind_idle_time(output: &str, now: Duration) -> Option<Duration> {
+    let mut idle = None;
+    let mut since = None;
+    for line in output.lines() {
+        match line.split_once('=') {
+            Some(("IdleHint", value)) => idle = Some(value == "yes"),
+            Some(("IdleSinceHint", value)) => since = Some(value.parse().ok()?),
+            _ => {}
+        }
+    }
+    if !idle? {
+        return Some(Duration::ZERO);
+    }
+    Some(now.saturating_sub(Duration::from_micros(since?)))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    };
+    use wayland_client::{
+        delegate_noop,
+        globals::{registry_queue_init, GlobalListContents},
+        protocol::{wl_registry, wl_seat},
+        Connection, Dispatch, QueueHandle,
+    };
+    use wayland_protocols::ext::idle_notify::v1::client::{
+        ext_idle_notification_v1, ext_idle_notifier_v1,
+    };
+    use x11rb::{
+        connection::Connection as _, protocol::screensaver, rust_connection::RustConnection,
+    };
+
+    pub enum Backend {
+        /// The instant the compositor reported the seat idle, shared with the dispatch thread.
+        Wayland {
+            idled_at: Arc<Mutex<Option<Instant>>>,
+            threshold: Duration,
+        },
+        X11 {
+            connection: Box<RustConnection>,
+            root: u32,
+        },
+        /// The last output of `loginctl`, refreshed by a thread of its own.
+        Logind { output: Arc<Mutex<Option<String>>> },
+    }
+
+    /// How often `loginctl` is asked, as often as the clocks check.
+    const LOGIND_POLL: Duration = Duration::from_secs(5);
+
+    impl Backend {
+        pub fn new(threshold: Duration) -> Self {
+            if let Some(idled_at) = spawn_wayland(threshold) {
+                return Backend::Wayland {
+                    idled_at,
+                    threshold,
+                };
+            }
+            if let Ok((connection, screen)) = x11rb::connect(None) {
+                let root = connection.setup().roots[screen].root;
+                return Backend::X11 {
+                    connection: Box::new(connection),
+                    root,
+                };
+            }
+            Backend::Logind {
+                output: spawn_logind(
+                    std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string()),
+                ),
+            }
+        }
+
+        pub fn idle_time(&mut self) -> Option<Duration> {
+            match self {
+                Backend::Wayland {
+                    idled_at,
+                    threshold,
+                } => Some(match *idled_at.lock().ok()? {
+                    Some(idled_at) => *threshold + idled_at.elapsed(),
+                    None => Duration::ZERO,
+                }),
+                Backend::X11 { connection, root } => {
+                    let info = screensaver::query_info(connection.as_ref(), *root)
+                        .ok()?
+                        .reply()
+                        .ok()?;
+                    Some(Duration::from_millis(info.ms_since_user_input.into()))
+                }
+                Backend::Logind { output } => {
+                    let output = output.lock().ok()?;
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+                    super::logind_idle_time(output.as_deref()?, now)
+                }
+            }
+        }
+    }
+
+    fn spawn_logind(session: String) -> Arc<Mutex<Option<String>>> {
+        let output = Arc::new(Mutex::new(None));
+        let latest = output.clone();
+        std::thread::spawn(move || loop {
+            let polled = std::process::Command::new("loginctl")
+                .args([
+                    "show-session",
+                    &session,
+                    "-p",
+                    "IdleHint",
+                    "-p",
+                    "IdleSinceHint",
+                ])
+                .output()
+                .ok()
+                .and_then(|output| String::from_utf8(output.stdout).ok());
+            match latest.lock() {
+                Ok(mut latest) => *latest = polled,
+                Err(_) => return,
+            }
+            std::thread::sleep(LOGIND_POLL);
+        });
+        output
+    }
+
+    struct WaylandState {
+        idled_at: Arc<Mutex<Option<Instant>>>,
+    }
+
+    fn spawn_wayland(threshold: Duration) -> Option<Arc<Mutex<Option<Instant>>>> {
+        let connection = Connection::connect_to_env().ok()?;
+        let (globals, mut queue) = registry_queue_init::<WaylandState>(&connection).ok()?;
+        let qh = queue.handle();
+        let seat: wl_seat::WlSeat = globals.bind(&qh, 1..=1, ()).ok()?;
+        let notifier: ext_idle_notifier_v1::ExtIdleNotifierV1 =
+            globals.bind(&qh, 1..=1, ()).ok()?;
+        notifier.get_idle_notification(
+            threshold.as_millis().try_into().unwrap_or(u32::MAX),
+            &seat,
+            &qh,
+            (),
+        );
+        let idled_at = Arc::new(Mutex::new(None));
+        let mut state = WaylandState {
+            idled_at: idled_at.clone(),
+        };
+        std::thread::spawn(move || while queue.blocking_dispatch(&mut state).is_ok() {});
+        Some(idled_at)
+    }
+
+    impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for WaylandState {
+        fn event(
+            _: &mut Self,
+            _: &wl_registry::WlRegistry,
+            _: wl_registry::Event,
+            _: &GlobalListContents,
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ext_idle_notification_v1::ExtIdleNotificationV1, ()> for WaylandState {
+        fn event(
+            state: &mut Self,
+            _: &ext_idle_notification_v1::ExtIdleNotificationV1,
+            event: ext_idle_notification_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let Ok(mut idled_at) = state.idled_at.lock() {
+                match event {
+                    ext_idle_notification_v1::Event::Idled => *idled_at = Some(Instant::now()),
+                    ext_idle_notification_v1::Event::Resumed => *idled_at = None,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    delegate_noop!(WaylandState: ignore wl_seat::WlSeat);
+    delegate_noop!(WaylandState: ext_idle_notifier_v1::ExtIdleNotifierV1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_logind_idle_hint() {
+        let now = Duration::from_secs(1_700_000_600);
+        let idle = "IdleHint=yes\nIdleSinceHint=1700000000000000\n";
+        assert_eq!(logind_idle_time(idle, now), Some(Duration::from_secs(600)));
+        let active = "IdleHint=no\nIdleSinceHint=0\n";
+        assert_eq!(logind_idle_time(active, now), Some(Duration::ZERO));
+        // A clock that went backwards is no idle time.
+        let future = "IdleHint=yes\nIdleSinceHint=1800000000000000\n";
+        assert_eq!(logind_idle_time(future, now), Some(Duration::ZERO));
+        assert_eq!(logind_idle_time("", now), None);
+        assert_eq!(logind_idle_time("IdleHint=yes\n", now), None);
+        assert_eq!(
+            logind_idle_time("IdleHint=yes\nIdleSinceHint=x\n", now),
+            None
+        );
+    }
+
+    #[test]
+    fn counts_as_away_from_the_threshold_on() {
+        let threshold = Duration::from_secs(300);
+        assert_eq!(away(None, threshold), None);
+        assert_eq!(away(Some(Duration::from_secs(299)), threshold), None);
+        assert_eq!(away(Some(threshold), threshold), Some(threshold));
+        let idle = Duration::from_secs(900);
+        assert_eq!(away(Some(idle), threshold), Some(idle));
+    }
+}
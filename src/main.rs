@@ -2,27 +2,66 @@
 
 use iced::{
     keyboard, time,
-    widget::{center, text, MouseArea},
+    widget::{center, column, text, MouseArea},
     Element, Subscription, Task, Theme,
 };
-use iced_gif::widget::gif;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, HashMap},
+    process::ExitCode,
+    sync::{Mutex, OnceLock, PoisonError},
     time::{Duration, Instant},
     vec::Vec,
 };
-use toml;
+
+mod api;
+mod appearance;
+mod blocker;
+mod calendar;
+mod dnd;
+mod dock;
+mod goal;
+mod history;
+mod hooks;
+mod http;
+mod idle;
+mod media;
+mod monitors;
+mod music;
+mod schedule;
+mod scripting;
+mod status;
+#[cfg(all(test, target_os = "linux"))]
+mod test_bus;
+mod time_format;
+mod urgency;
 
 const CONFIG_PATH: &str = "tomato.toml";
+/// How long a clock has to stay in place before a drag counts as over.
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
 
-fn main() -> iced::Result {
-    iced::daemon(AppDaemon::title, AppDaemon::update, AppDaemon::view)
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("status") => return status::status(&args[1..]),
+        Some("action") => return status::action(&args[1..]),
+        _ => {}
+    }
+    let result = iced::daemon(AppDaemon::title, AppDaemon::update, AppDaemon::view)
         .subscription(AppDaemon::subscription)
         .theme(AppDaemon::theme)
-        .run_with(AppDaemon::new)
+        .run_with(AppDaemon::new);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(try_from = "ColorValue")]
 struct Color {
     r: f32,
     g: f32,
@@ -30,25 +69,204 @@ struct Color {
     a: f32,
 }
 
+/// A color as written in the config file: `{r, g, b, a}` or a hex string such as `"#268bd2"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Hex(String),
+    Rgba { r: f32, g: f32, b: f32, a: f32 },
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Rgba { r, g, b, a } => Ok(Color { r, g, b, a }),
+            ColorValue::Hex(hex) => {
+                let digits = hex.strip_prefix('#').unwrap_or(&hex);
+                let channel = |i: usize| {
+                    digits
+                        .get(i..i + 2)
+                        .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                        .map(|channel| f32::from(channel) / 255.0)
+                };
+                let invalid = || format!("invalid hex color {hex:?}");
+                // `from_str_radix` would also take a sign.
+                if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(invalid());
+                }
+                match (digits.len(), channel(0), channel(2), channel(4)) {
+                    (6, Some(r), Some(g), Some(b)) => Ok(Color { r, g, b, a: 1.0 }),
+                    (8, Some(r), Some(g), Some(b)) => Ok(Color {
+                        r,
+                        g,
+                        b,
+                        a: channel(6).ok_or_else(invalid)?,
+                    }),
+                    _ => Err(invalid()),
+                }
+            }
+        }
+    }
+}
+
 impl From<Color> for iced::Color {
     fn from(c: Color) -> Self {
         iced::Color::from_rgba(c.r, c.g, c.b, c.a)
     }
 }
 
+/// How a reminder picks among several texts and images.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum Order {
+    #[default]
+    Rotation,
+    Random,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 struct ReminderConfig {
+    /// Texts may contain `{completed_today}`, `{next_phase}` and `{task}`.
     text: Option<String>,
+    /// More texts to pick from along with `text`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    texts: Vec<String>,
     color: Option<Color>,
     font_size: Option<u16>,
     image_path: Option<String>,
+    /// More images to pick from along with `image_path`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    image_paths: Vec<String>,
+    order: Option<Order>,
+    /// Shows the text below the image instead of the image alone.
+    caption: Option<bool>,
     width: Option<u16>,
     height: Option<u16>,
+    /// `"clock"` for the monitor showing the clock, `"all"`, or an output name such as `"DP-1"`.
+    /// Unset uses the monitor the window manager picks.
+    monitor: Option<String>,
+}
+
+/// An additional clock window. Missing fields fall back to the main clock's settings.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+struct ClockConfig {
+    label: Option<String>,
+    mode: Option<Mode>,
+    pomodoro_minutes: Option<u64>,
+    /// The time of day, e.g. `"17:30"`, that `until` mode counts down to.
+    until: Option<String>,
+    position: Option<[f32; 2]>,
+    /// If set, `position` is relative to this monitor.
+    position_monitor: Option<String>,
+    /// Keeps the clock docked to an edge or corner of its monitor.
+    anchor: Option<dock::Anchor>,
+    /// The window size before scaling, 150×45 if unset.
+    size: Option<[f32; 2]>,
+    scale: Option<f32>,
+    /// `"monospace"`, `"serif"`, `"sans-serif"`, or the name of an installed or loaded font.
+    font_family: Option<String>,
+    time_format: Option<time_format::TimeFormat>,
+    /// The name of a theme from `themes`; its colors take precedence over the color indices.
+    theme: Option<String>,
+    urgency: Option<Vec<urgency::UrgencyRule>>,
+    stop_text_color_index: Option<usize>,
+    run_text_color_index: Option<usize>,
+    stop_background_color_index: Option<usize>,
+    run_background_color_index: Option<usize>,
+    reminder: Option<ReminderConfig>,
+    /// Start a break of this length after each pomodoro. Not inherited from the main clock.
+    break_minutes: Option<u64>,
+}
+
+/// Docks a clock to the nearest edge or corner when it is dropped close to one.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+struct SnapConfig {
+    distance: f32,
+    /// The gap kept between a docked clock and the screen edge.
+    margin: f32,
+}
+
+impl Default for SnapConfig {
+    fn default() -> Self {
+        Self {
+            distance: 24.0,
+            margin: 8.0,
+        }
+    }
+}
+
+/// Replaces the dismissible reminder during breaks with fullscreen overlays on every monitor.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+struct StrictBreakConfig {
+    skips_per_day: u32,
+    /// Delays the break by this many minutes; no postponing if unset.
+    postpone_minutes: Option<u64>,
+    /// How often a single break may be postponed, 1 if unset.
+    max_postpones: Option<u32>,
+    background: Option<Color>,
+}
+
+/// A named set of colors. Unset colors fall back to the color indices, the break colors to the
+/// run colors.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+struct ThemeConfig {
+    stop_text: Option<Color>,
+    stop_background: Option<Color>,
+    run_text: Option<Color>,
+    run_background: Option<Color>,
+    break_text: Option<Color>,
+    break_background: Option<Color>,
+    /// Replaces the color of the reminder text.
+    reminder: Option<Color>,
+}
+
+/// The colors a clock is drawn with, resolved from its theme and color indices.
+#[derive(Debug, Clone, Copy)]
+struct Palette {
+    stop_text: iced::Color,
+    stop_background: iced::Color,
+    run_text: iced::Color,
+    run_background: iced::Color,
+    break_text: iced::Color,
+    break_background: iced::Color,
+    reminder: Option<iced::Color>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 struct TomatoConfig {
     position: Option<[f32; 2]>,
+    /// Starts the main clock in `until` mode, counting down to this time of day.
+    until: Option<String>,
+    position_monitor: Option<String>,
+    anchor: Option<dock::Anchor>,
+    size: Option<[f32; 2]>,
+    scale: Option<f32>,
+    font_family: Option<String>,
+    /// Font files loaded at startup, so their families can be used in `font_family`.
+    #[serde(default)]
+    font_paths: Vec<String>,
+    time_format: Option<time_format::TimeFormat>,
+    theme: Option<String>,
+    #[serde(default)]
+    themes: BTreeMap<String, ThemeConfig>,
+    /// Recolors running pomodoros as they near their end.
+    #[serde(default)]
+    urgency: Vec<urgency::UrgencyRule>,
+    /// A directory of theme files, each named after its theme (`solarized.toml`), `themes` if
+    /// unset.
+    themes_dir: Option<String>,
+    /// A directory of Rhai scripts, `scripts` if unset.
+    scripts_dir: Option<String>,
+    /// Follow the desktop color scheme with these themes.
+    light_theme: Option<String>,
+    dark_theme: Option<String>,
+    /// The themes loaded from `themes_dir`, which are not written back to the config file.
+    #[serde(skip)]
+    imported_themes: BTreeMap<String, ThemeConfig>,
     stop_text_color_index: usize,
     run_text_color_index: usize,
     stop_background_color_index: usize,
@@ -56,12 +274,47 @@ struct TomatoConfig {
     text_colors: Vec<Color>,
     background_colors: Vec<Color>,
     reminder: ReminderConfig,
+    #[serde(default)]
+    clocks: Vec<ClockConfig>,
+    /// Pause running pomodoros after this many minutes without keyboard or mouse input.
+    idle_minutes: Option<u64>,
+    break_minutes: Option<u64>,
+    strict_break: Option<StrictBreakConfig>,
+    snap: Option<SnapConfig>,
+    calendar: Option<calendar::CalendarConfig>,
+    goal: Option<goal::GoalConfig>,
+    work_hours: Option<schedule::WorkHoursConfig>,
+    hooks: Option<hooks::HooksConfig>,
+    /// Pauses and resumes media players between focus and breaks.
+    music: Option<music::MusicConfig>,
+    /// Turns on the desktop Do-Not-Disturb while a clock is focusing.
+    do_not_disturb: Option<dnd::Backend>,
+    /// Blocks distracting sites through a local proxy while a clock is focusing.
+    blocker: Option<blocker::BlockerConfig>,
+    /// Serves the clocks' state and takes actions over HTTP on a loopback address.
+    api: Option<api::ApiConfig>,
 }
 
 impl Default for TomatoConfig {
     fn default() -> Self {
         Self {
             position: None,
+            until: None,
+            position_monitor: None,
+            anchor: None,
+            size: None,
+            scale: None,
+            font_family: None,
+            font_paths: Vec::new(),
+            time_format: None,
+            theme: None,
+            themes: BTreeMap::new(),
+            urgency: Vec::new(),
+            themes_dir: None,
+            scripts_dir: None,
+            light_theme: None,
+            dark_theme: None,
+            imported_themes: BTreeMap::new(),
             stop_text_color_index: 0,
             run_text_color_index: 0,
             stop_background_color_index: 0,
@@ -80,43 +333,268 @@ impl Default for TomatoConfig {
             }],
             reminder: ReminderConfig {
                 text: None,
+                texts: Vec::new(),
                 color: None,
                 font_size: None,
                 image_path: None,
+                image_paths: Vec::new(),
+                order: None,
+                caption: None,
                 width: None,
                 height: None,
+                monitor: None,
             },
+            clocks: Vec::new(),
+            idle_minutes: None,
+            break_minutes: None,
+            strict_break: None,
+            snap: None,
+            calendar: None,
+            goal: None,
+            work_hours: None,
+            hooks: None,
+            music: None,
+            do_not_disturb: None,
+            blocker: None,
+            api: None,
+        }
+    }
+}
+
+impl TomatoConfig {
+    fn text_color(&self, index: usize) -> iced::Color {
+        self.text_colors[index.min(self.text_colors.len() - 1)].into()
+    }
+
+    fn background_color(&self, index: usize) -> iced::Color {
+        self.background_colors[index.min(self.background_colors.len() - 1)].into()
+    }
+
+    /// Loads the theme files from `themes_dir`. Themes in the config file take precedence.
+    fn import_themes(&mut self) {
+        let dir = self.themes_dir.as_deref().unwrap_or("themes");
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            let Some(name) = path
+                .file_stem()
+                .filter(|_| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "toml")
+                })
+                .map(|name| name.to_string_lossy().into_owned())
+            else {
+                continue;
+            };
+            match std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|toml_str| toml::from_str(&toml_str).map_err(|e| e.to_string()))
+            {
+                Ok(theme) => {
+                    self.imported_themes.insert(name, theme);
+                }
+                Err(e) => eprintln!("Failed to load theme {}: {e}", path.display()),
+            }
+        }
+    }
+
+    /// All theme names in the order they are cycled through.
+    fn theme_names(&self) -> Vec<&str> {
+        let mut names = self
+            .themes
+            .keys()
+            .chain(self.imported_themes.keys())
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    fn theme_named(&self, name: &str) -> Option<&ThemeConfig> {
+        self.themes
+            .get(name)
+            .or_else(|| self.imported_themes.get(name))
+    }
+
+    /// The colors of `clock`, from `scheme_theme` instead of its own theme while the desktop
+    /// color scheme picks one.
+    fn palette(&self, clock: &ClockConfig, scheme_theme: Option<&str>) -> Palette {
+        let theme = scheme_theme
+            .or(clock.theme.as_deref())
+            .and_then(|name| self.theme_named(name))
+            .cloned()
+            .unwrap_or_default();
+        let pick = |color: Option<Color>, fallback: iced::Color| color.map_or(fallback, Into::into);
+        let run_text = pick(
+            theme.run_text,
+            self.text_color(clock.run_text_color_index.unwrap_or_default()),
+        );
+        let run_background = pick(
+            theme.run_background,
+            self.background_color(clock.run_background_color_index.unwrap_or_default()),
+        );
+        Palette {
+            stop_text: pick(
+                theme.stop_text,
+                self.text_color(clock.stop_text_color_index.unwrap_or_default()),
+            ),
+            stop_background: pick(
+                theme.stop_background,
+                self.background_color(clock.stop_background_color_index.unwrap_or_default()),
+            ),
+            run_text,
+            run_background,
+            break_text: pick(theme.break_text, run_text),
+            break_background: pick(theme.break_background, run_background),
+            reminder: theme.reminder.map(Into::into),
+        }
+    }
+
+    /// The theme for the desktop color scheme, if it is followed.
+    fn scheme_theme(&self, scheme: Option<appearance::ColorScheme>) -> Option<&str> {
+        match scheme? {
+            appearance::ColorScheme::Light => self.light_theme.as_deref(),
+            appearance::ColorScheme::Dark => self.dark_theme.as_deref(),
+        }
+    }
+
+    /// The main clock expressed as a `ClockConfig`, so every window is built the same way.
+    fn main_clock(&self) -> ClockConfig {
+        ClockConfig {
+            label: None,
+            mode: self.until.as_ref().map(|_| Mode::Until),
+            pomodoro_minutes: None,
+            until: self.until.clone(),
+            position: self.position,
+            position_monitor: self.position_monitor.clone(),
+            anchor: self.anchor,
+            size: self.size,
+            scale: self.scale,
+            font_family: self.font_family.clone(),
+            time_format: self.time_format.clone(),
+            theme: self.theme.clone(),
+            urgency: None,
+            stop_text_color_index: Some(self.stop_text_color_index),
+            run_text_color_index: Some(self.run_text_color_index),
+            stop_background_color_index: Some(self.stop_background_color_index),
+            run_background_color_index: Some(self.run_background_color_index),
+            reminder: None,
+            break_minutes: self.break_minutes,
+        }
+    }
+
+    /// Fills the unset fields of `clock` from the main clock and clamps the color indices.
+    fn resolve(&self, clock: &ClockConfig) -> ClockConfig {
+        let clamp = |index: Option<usize>, default: usize, len: usize| {
+            Some(index.unwrap_or(default).min(len - 1))
+        };
+        ClockConfig {
+            label: clock.label.clone(),
+            mode: Some(clock.mode.unwrap_or_default()),
+            pomodoro_minutes: Some(clock.pomodoro_minutes.unwrap_or(25).clamp(5, 60)),
+            until: clock.until.clone(),
+            position: clock.position,
+            position_monitor: clock.position_monitor.clone(),
+            anchor: clock.anchor,
+            size: clock.size.or(self.size),
+            scale: Some(clock.scale.or(self.scale).unwrap_or(1.0).clamp(0.5, 4.0)),
+            font_family: clock.font_family.clone().or(self.font_family.clone()),
+            time_format: clock.time_format.clone().or(self.time_format.clone()),
+            theme: clock.theme.clone().or(self.theme.clone()),
+            urgency: clock
+                .urgency
+                .clone()
+                .or_else(|| Some(self.urgency.clone()).filter(|rules| !rules.is_empty())),
+            stop_text_color_index: clamp(
+                clock.stop_text_color_index,
+                self.stop_text_color_index,
+                self.text_colors.len(),
+            ),
+            run_text_color_index: clamp(
+                clock.run_text_color_index,
+                self.run_text_color_index,
+                self.text_colors.len(),
+            ),
+            stop_background_color_index: clamp(
+                clock.stop_background_color_index,
+                self.stop_background_color_index,
+                self.background_colors.len(),
+            ),
+            run_background_color_index: clamp(
+                clock.run_background_color_index,
+                self.run_background_color_index,
+                self.background_colors.len(),
+            ),
+            reminder: clock.reminder.clone(),
+            break_minutes: clock.break_minutes,
         }
     }
 }
 
 struct AppDaemon {
-    windows: (
-        (iced::window::Id, TomatoClock),
-        Option<(iced::window::Id, Reminder)>,
-    ),
-    picture_data: Option<Picture>,
+    /// The first clock is the main one; closing it exits the program.
+    clocks: Vec<(iced::window::Id, TomatoClock)>,
+    reminders: Vec<(iced::window::Id, Reminder)>,
+    break_overlays: Vec<(iced::window::Id, BreakOverlay)>,
+    /// Strict breaks skipped on the given day, counted across restarts through the history.
+    skipped_breaks: (chrono::NaiveDate, u32),
+    idle_monitor: Option<idle::IdleMonitor>,
+    modifiers: keyboard::Modifiers,
+    media: media::Cache,
+    /// The desktop color scheme, once reported.
+    color_scheme: Option<appearance::ColorScheme>,
+    calendar: Option<calendar::Calendar>,
+    goal: Option<goal::Goal>,
+    schedule: Option<schedule::Schedule>,
+    scripts: Option<scripting::Scripts>,
+    music: Option<music::Music>,
+    do_not_disturb: Option<dnd::DoNotDisturb>,
+    blocker: Option<blocker::Blocker>,
+    api: Option<api::Api>,
+    /// Whether it is outside the working hours, where reminders stay quiet.
+    off_hours: bool,
+    /// The day the first pomodoro was started automatically.
+    auto_started_on: Option<chrono::NaiveDate>,
+    /// The scale factor of the clock windows, which turns monitor pixels into window
+    /// coordinates.
+    scale_factor: f32,
+    /// Queried again only when the scale factor changes or a clock was dragged, as every query
+    /// is a round trip to the X server.
+    monitors: Vec<monitors::Monitor>,
     exist_entity: bool,
     tomato_config: TomatoConfig,
 }
 
-enum Picture {
-    ImageHandle(iced::widget::image::Handle),
-    GifFrams(gif::Frames),
-}
-
+#[derive(Clone)]
 struct Reminder {
+    clock: iced::window::Id,
     text: String,
+    picture: Option<media::Source>,
+    caption: bool,
+    /// When the reminder appeared, to play animated pictures from the start.
+    shown_at: Instant,
     color: iced::Color,
     font_size: u16,
     width: Option<u16>,
     height: Option<u16>,
 }
 
+/// A fullscreen window covering one monitor while `clock` is on a strict break.
+struct BreakOverlay {
+    clock: iced::window::Id,
+    reminder: Reminder,
+}
+
 impl Default for Reminder {
     fn default() -> Self {
         Self {
+            clock: iced::window::Id::unique(),
             text: ":) Time out!!!!!".to_string(),
+            picture: None,
+            caption: false,
+            shown_at: Instant::now(),
             color: iced::Color::from_rgba(0.8, 1.0, 0.0, 0.8),
             font_size: 180,
             width: None,
@@ -130,17 +608,101 @@ struct TomatoClock {
     state: State,
     mode: Mode,
     pomodoro_duration: Duration,
-    run_background_color: iced::Color,
-    stop_background_color: iced::Color,
-    run_text_color: iced::Color,
-    stop_text_color: iced::Color,
+    palette: Palette,
+    /// The settings this clock was opened with, kept up to date so they can be saved on exit.
+    config: ClockConfig,
+    font: iced::Font,
+    /// Where the window currently is on screen.
+    position: Option<iced::Point>,
+    /// When the window last moved, to snap it once a drag has ended.
+    last_moved: Option<Instant>,
+    /// How many reminders the clock has shown, to rotate through their texts and images.
+    reminders_shown: usize,
+    session: Option<Session>,
+    /// Idle time counted before an automatic pause, waiting for the user to keep or discard it.
+    idle_prompt: Option<Duration>,
+    phase: Phase,
+    break_duration: Option<Duration>,
+    /// How often the current break has been postponed.
+    postpones: u32,
+    /// The theme of the desktop color scheme, which is never saved over `config.theme`.
+    scheme_theme: Option<String>,
+    /// Whether the lap list is shown below the time.
+    show_laps: bool,
+    /// The time of day `until` mode counts down to.
+    until: Option<chrono::NaiveTime>,
+    /// The moment a running `until` countdown ends.
+    target: Option<chrono::DateTime<chrono::Local>>,
+    /// The digits of a time of day being typed in `until` mode.
+    entry: Option<String>,
+    /// A calendar event the pomodoro would run into or is held for.
+    notice: Option<calendar::Notice>,
+    /// The start of an event the user resumed the pomodoro during, which no longer holds it.
+    hold_skipped: Option<chrono::DateTime<chrono::Local>>,
+    /// Today's progress towards the daily goal, shown on the main clock.
+    progress: Option<String>,
+    /// Faded out outside the working hours.
+    dimmed: bool,
+    /// Events for the hook commands, run by the daemon after every update.
+    hook_events: Vec<(hooks::Event, hooks::Details)>,
+    /// The last script error, shown until the next key press on the clock.
+    script_error: Option<String>,
 }
 
-#[derive(Default)]
+/// The run being timed, written to the history once it ends.
+struct Session {
+    started_at: u64,
+    focused: Duration,
+    discarded_idle: Duration,
+    /// The stopwatch time at every lap.
+    laps: Vec<Duration>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            started_at: history::unix_now(),
+            focused: Duration::ZERO,
+            discarded_idle: Duration::ZERO,
+            laps: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
 enum Mode {
     #[default]
     Pomodoro,
     Stopwatch,
+    /// Counts down to the time of day in `until`.
+    Until,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Pomodoro => "pomodoro",
+            Mode::Stopwatch => "stopwatch",
+            Mode::Until => "until",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Phase {
+    #[default]
+    Focus,
+    Break,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Focus => "focus",
+            Phase::Break => "break",
+        }
+    }
 }
 
 #[derive(Default)]
@@ -154,24 +716,345 @@ enum State {
 
 #[derive(Debug, Clone, Copy)]
 enum Message {
-    Toggle,
-    ToggleMode,
-    Reset,
+    Toggle(iced::window::Id),
+    ToggleMode(iced::window::Id),
+    Reset(iced::window::Id),
     Tick(Instant),
-    IncreasePomodoroDuration,
-    DecreasePomodoroDuration,
+    IncreasePomodoroDuration(iced::window::Id),
+    DecreasePomodoroDuration(iced::window::Id),
     Shutdown,
-    StartDragging,
-    ChangeTextColor,
-    ChangeBackgroundColor,
-    TimeOut,
-    CloseReminder,
-    EarlyTermination,
+    StartDragging(iced::window::Id),
+    ChangeTextColor(iced::window::Id),
+    ChangeBackgroundColor(iced::window::Id),
+    CycleTheme(iced::window::Id),
+    TimeOut(iced::window::Id),
+    CloseReminder(iced::window::Id),
+    EarlyTermination(iced::window::Id),
+    NewClock,
+    CloseClock(iced::window::Id),
+    Moved(iced::window::Id, iced::Point),
+    CheckIdle,
+    KeepIdle(iced::window::Id),
+    DiscardIdle(iced::window::Id),
+    SkipBreak(iced::window::Id),
+    PostponeBreak(iced::window::Id),
+    SettleDrag,
+    /// The scale factor of a newly opened clock window.
+    ScaleFactorChanged(iced::window::Id, f32),
+    /// Docks the clock towards the given edge, walking it around the corners.
+    Dock(iced::window::Id, dock::Anchor),
+    ModifiersChanged(keyboard::Modifiers),
+    /// Scrolled by the given number of lines over a window.
+    Scrolled(iced::window::Id, f32),
+    ColorSchemeChanged(appearance::ColorScheme),
+    /// Types a digit of the time of day to count down to.
+    TypeDigit(iced::window::Id, u8),
+    EraseDigit(iced::window::Id),
+    /// Applies the working hours.
+    CheckSchedule,
+    /// Celebrates the daily goal with a reminder.
+    GoalReached,
+    /// Looks for calendar events to hold pomodoros for or warn about.
+    CheckCalendar,
+    /// Shortens the pomodoro to end when the next calendar event starts.
+    FitToCalendar(iced::window::Id),
+    /// Records a lap of a running stopwatch.
+    Lap(iced::window::Id),
+    ToggleLaps(iced::window::Id),
+    /// An action requested over the HTTP API.
+    Api(api::Command),
+    /// A reminder picture finished decoding in the background.
+    PictureLoaded,
+    Animate,
+}
+
+impl Message {
+    /// The window the message is about.
+    fn window(self) -> Option<iced::window::Id> {
+        match self {
+            Message::Toggle(id)
+            | Message::ToggleMode(id)
+            | Message::Reset(id)
+            | Message::IncreasePomodoroDuration(id)
+            | Message::DecreasePomodoroDuration(id)
+            | Message::StartDragging(id)
+            | Message::ChangeTextColor(id)
+            | Message::ChangeBackgroundColor(id)
+            | Message::CycleTheme(id)
+            | Message::TimeOut(id)
+            | Message::CloseReminder(id)
+            | Message::EarlyTermination(id)
+            | Message::CloseClock(id)
+            | Message::Moved(id, _)
+            | Message::KeepIdle(id)
+            | Message::DiscardIdle(id)
+            | Message::SkipBreak(id)
+            | Message::PostponeBreak(id)
+            | Message::Dock(id, _)
+            | Message::Scrolled(id, _)
+            | Message::TypeDigit(id, _)
+            | Message::EraseDigit(id)
+            | Message::FitToCalendar(id)
+            | Message::Lap(id)
+            | Message::ToggleLaps(id)
+            | Message::ScaleFactorChanged(id, _) => Some(id),
+            Message::Tick(_)
+            | Message::Shutdown
+            | Message::NewClock
+            | Message::CheckIdle
+            | Message::SettleDrag
+            | Message::ModifiersChanged(_)
+            | Message::ColorSchemeChanged(_)
+            | Message::CheckSchedule
+            | Message::GoalReached
+            | Message::CheckCalendar
+            | Message::Api(_)
+            | Message::PictureLoaded
+            | Message::Animate => None,
+        }
+    }
+
+    /// The name scripts know the message by.
+    fn name(&self) -> &'static str {
+        match self {
+            Message::Toggle(_) => "Toggle",
+            Message::ToggleMode(_) => "ToggleMode",
+            Message::Reset(_) => "Reset",
+            Message::Tick(_) => "Tick",
+            Message::IncreasePomodoroDuration(_) => "IncreasePomodoroDuration",
+            Message::DecreasePomodoroDuration(_) => "DecreasePomodoroDuration",
+            Message::Shutdown => "Shutdown",
+            Message::StartDragging(_) => "StartDragging",
+            Message::ChangeTextColor(_) => "ChangeTextColor",
+            Message::ChangeBackgroundColor(_) => "ChangeBackgroundColor",
+            Message::CycleTheme(_) => "CycleTheme",
+            Message::TimeOut(_) => "TimeOut",
+            Message::CloseReminder(_) => "CloseReminder",
+            Message::EarlyTermination(_) => "EarlyTermination",
+            Message::NewClock => "NewClock",
+            Message::CloseClock(_) => "CloseClock",
+            Message::Moved(..) => "Moved",
+            Message::CheckIdle => "CheckIdle",
+            Message::KeepIdle(_) => "KeepIdle",
+            Message::DiscardIdle(_) => "DiscardIdle",
+            Message::SkipBreak(_) => "SkipBreak",
+            Message::PostponeBreak(_) => "PostponeBreak",
+            Message::SettleDrag => "SettleDrag",
+            Message::Dock(..) => "Dock",
+            Message::ModifiersChanged(_) => "ModifiersChanged",
+            Message::Scrolled(..) => "Scrolled",
+            Message::ColorSchemeChanged(_) => "ColorSchemeChanged",
+            Message::ScaleFactorChanged(..) => "ScaleFactorChanged",
+            Message::TypeDigit(..) => "TypeDigit",
+            Message::EraseDigit(_) => "EraseDigit",
+            Message::CheckSchedule => "CheckSchedule",
+            Message::GoalReached => "GoalReached",
+            Message::CheckCalendar => "CheckCalendar",
+            Message::FitToCalendar(_) => "FitToCalendar",
+            Message::Lap(_) => "Lap",
+            Message::ToggleLaps(_) => "ToggleLaps",
+            Message::Api(_) => "Api",
+            Message::PictureLoaded => "PictureLoaded",
+            Message::Animate => "Animate",
+        }
+    }
+
+    /// Whether scripts are told about the message. Frequent messages would run them many times
+    /// a second.
+    fn reaches_scripts(&self) -> bool {
+        !matches!(
+            self,
+            Message::Tick(_)
+                | Message::Animate
+                | Message::Moved(..)
+                | Message::Scrolled(..)
+                | Message::SettleDrag
+                | Message::ModifiersChanged(_)
+                | Message::PictureLoaded
+                | Message::ScaleFactorChanged(..)
+                | Message::CheckIdle
+                | Message::CheckSchedule
+                | Message::CheckCalendar
+        )
+    }
+}
+
+fn today() -> chrono::NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+/// Parses a time of day such as `17:30`, or `1730` as typed on the clock.
+fn parse_time_of_day(time: &str) -> Option<chrono::NaiveTime> {
+    chrono::NaiveTime::parse_from_str(time, "%H:%M")
+        .ok()
+        .or_else(|| {
+            let split = time.len().checked_sub(2).filter(|split| *split > 0)?;
+            let hours = time.get(..split)?.parse().ok()?;
+            let minutes = time.get(split..)?.parse().ok()?;
+            chrono::NaiveTime::from_hms_opt(hours, minutes, 0)
+        })
+}
+
+/// The next time the wall clock shows `time`, today or tomorrow, as
+/// [`calendar::resolve_time`] places it around daylight saving changes.
+fn next_occurrence(
+    time: chrono::NaiveTime,
+    now: chrono::DateTime<chrono::Local>,
+) -> Option<chrono::DateTime<chrono::Local>> {
+    (0..=1)
+        .filter_map(|days| {
+            let local = now
+                .date_naive()
+                .checked_add_days(chrono::Days::new(days))?
+                .and_time(time);
+            calendar::resolve_time(&chrono::Local, local)
+        })
+        .find(|occurrence| *occurrence > now)
+}
+
+/// Reads `--until HH:MM` from the command line.
+fn until_argument() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--until") {
+            Some("") => return args.next(),
+            Some(value) if value.starts_with('=') => return Some(value[1..].to_string()),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl ReminderConfig {
+    fn texts(&self) -> Vec<&str> {
+        self.text
+            .iter()
+            .chain(&self.texts)
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn image_paths(&self) -> Vec<&str> {
+        self.image_path
+            .iter()
+            .chain(&self.image_paths)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// The index of the text or image for the reminder after `shown` earlier ones.
+    fn pick(&self, count: usize, shown: usize) -> Option<usize> {
+        match (count, self.order.unwrap_or_default()) {
+            (0, _) => None,
+            (_, Order::Rotation) => Some(shown % count),
+            (_, Order::Random) => Some(fastrand::usize(..count)),
+        }
+    }
+}
+
+/// Fills in the placeholders of a reminder text.
+fn render_template(template: &str, next_phase: Phase, task: &str) -> String {
+    let mut rendered = template
+        .replace("{next_phase}", next_phase.name())
+        .replace("{task}", task);
+    if rendered.contains("{completed_today}") {
+        rendered = rendered.replace(
+            "{completed_today}",
+            &history::completed_on(today()).to_string(),
+        );
+    }
+    rendered
+}
+
+/// The height added above the time for a clock's label, before scaling.
+const LABEL_HEIGHT: f32 = 15.0;
+
+/// How far clocks fade outside the working hours.
+const DIMMED_ALPHA: f32 = 0.35;
+
+/// The number of laps shown in the lap list, and the height of each line before scaling.
+const VISIBLE_LAPS: usize = 5;
+const LAP_HEIGHT: f32 = 14.0;
+
+/// The number, lap time and split time of the last [`VISIBLE_LAPS`] of the stopwatch times at
+/// every lap.
+fn recent_laps(splits: &[Duration]) -> impl Iterator<Item = (usize, Duration, Duration)> + '_ {
+    let previous = std::iter::once(Duration::ZERO).chain(splits.iter().copied());
+    splits
+        .iter()
+        .zip(previous)
+        .enumerate()
+        .skip(splits.len().saturating_sub(VISIBLE_LAPS))
+        .map(|(index, (split, previous))| (index + 1, split.saturating_sub(previous), *split))
+}
+
+fn clock_size(config: &ClockConfig) -> iced::Size {
+    let [width, height] = config.size.unwrap_or([150f32, 45f32]);
+    let label_height = if config.label.is_some() {
+        LABEL_HEIGHT
+    } else {
+        0.0
+    };
+    iced::Size::new(width, height + label_height) * config.scale.unwrap_or(1.0)
+}
+
+/// The largest text size at which `content` fits into `bounds`, estimating the glyph width of
+/// digits at about half the text size.
+fn fit_text_size(content: &str, bounds: iced::Size) -> f32 {
+    let chars = content.chars().count().max(1) as f32;
+    (bounds.width / (chars * 0.47)).min(bounds.height * 0.89)
+}
+
+fn font(family: Option<&str>) -> iced::Font {
+    match family {
+        None | Some("sans-serif") => iced::Font::DEFAULT,
+        Some("monospace") => iced::Font::MONOSPACE,
+        Some("serif") => iced::Font {
+            family: iced::font::Family::Serif,
+            ..iced::Font::DEFAULT
+        },
+        Some(name) => iced::Font::with_name(family_name(name)),
+    }
+}
+
+/// Fonts need a static family name, so every name is leaked once and reused by the clocks
+/// opened later.
+fn family_name(name: &str) -> &'static str {
+    static NAMES: OnceLock<Mutex<HashMap<String, &'static str>>> = OnceLock::new();
+    let mut names = NAMES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    names
+        .entry(name.to_string())
+        .or_insert_with(|| Box::leak(name.to_string().into_boxed_str()))
+}
+
+fn open_clock(
+    config: &ClockConfig,
+    position: Option<iced::Point>,
+) -> (iced::window::Id, Task<iced::window::Id>) {
+    iced::window::open(iced::window::Settings {
+        size: clock_size(config),
+        position: if let Some(position) = position {
+            iced::window::Position::Specific(position)
+        } else {
+            iced::window::Position::Centered
+        },
+        resizable: false,
+        decorations: false,
+        transparent: true,
+        level: iced::window::Level::AlwaysOnTop,
+        icon: Some(
+            iced::window::icon::from_file_data(include_bytes!("../tomato.ico"), None).unwrap(),
+        ),
+        ..Default::default()
+    })
 }
 
 impl AppDaemon {
     fn new() -> (Self, Task<Message>) {
-        let (mut tomato_config, exist_entity) =
+        let (mut tomato_config, exist_entity): (TomatoConfig, bool) =
             if let Ok(toml_str) = std::fs::read_to_string(CONFIG_PATH) {
                 (
                     toml::from_str(&toml_str).expect("Failed to parse config file"),
@@ -181,375 +1064,1960 @@ impl AppDaemon {
                 (TomatoConfig::default(), false)
             };
         // println!("Tomato config: {:#?}", tomato_config);
-        let picture_data = if let Some(path) = &tomato_config.reminder.image_path {
-            if matches!(
-                std::path::Path::new(path)
-                    .extension()
-                    .map(|ext| ext.to_str()),
-                Some(Some("gif"))
-            ) {
-                Some(Picture::GifFrams(
-                    gif::Frames::from_bytes(
-                        std::fs::read(path).expect("Failed to read image file"),
-                    )
-                    .expect("Failed to decode gif file"),
-                ))
-            } else {
-                Some(Picture::ImageHandle(path.into()))
+        tomato_config.import_themes();
+        let mut daemon = Self::with_config(tomato_config, exist_entity);
+        let mut configs = vec![daemon.tomato_config.main_clock()];
+        if let Some(until) = until_argument() {
+            configs[0].mode = Some(Mode::Until);
+            configs[0].until = Some(until);
+        }
+        configs.extend(daemon.tomato_config.clocks.iter().cloned());
+        let fonts = daemon
+            .tomato_config
+            .font_paths
+            .iter()
+            .filter_map(|path| match std::fs::read(path) {
+                Ok(bytes) => Some(iced::font::load(bytes).then(|result| {
+                    if let Err(e) = result {
+                        eprintln!("Failed to load font: {e:?}");
+                    }
+                    Task::none()
+                })),
+                Err(e) => {
+                    eprintln!("Failed to read font file {path}: {e}");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let opens = configs
+            .iter()
+            .map(|config| daemon.spawn_clock(config))
+            .collect::<Vec<_>>();
+        let opens = daemon.track_goal(Task::batch(opens));
+        daemon.report_script_errors();
+        let schedule = daemon.follow_schedule();
+        (daemon, Task::batch(fonts).chain(opens).chain(schedule))
+    }
+
+    /// The daemon for `tomato_config`, before it opens any window.
+    fn with_config(tomato_config: TomatoConfig, exist_entity: bool) -> Self {
+        let idle_monitor = tomato_config
+            .idle_minutes
+            .map(|minutes| idle::IdleMonitor::new(Duration::from_secs(minutes * 60)));
+        let skipped_breaks = history::skipped_breaks_on(today());
+        Self {
+            clocks: Vec::new(),
+            reminders: Vec::new(),
+            break_overlays: Vec::new(),
+            skipped_breaks: (today(), skipped_breaks),
+            idle_monitor,
+            modifiers: keyboard::Modifiers::default(),
+            media: media::Cache::default(),
+            color_scheme: None,
+            calendar: tomato_config
+                .calendar
+                .as_ref()
+                .map(|config| calendar::Calendar::new(&config.path)),
+            goal: tomato_config.goal.clone().map(goal::Goal::new),
+            schedule: tomato_config
+                .work_hours
+                .as_ref()
+                .map(schedule::Schedule::new),
+            scripts: scripting::Scripts::load(std::path::Path::new(
+                tomato_config.scripts_dir.as_deref().unwrap_or("scripts"),
+            )),
+            music: tomato_config.music.as_ref().map(music::Music::new),
+            do_not_disturb: tomato_config.do_not_disturb.map(dnd::DoNotDisturb::new),
+            blocker: tomato_config.blocker.as_ref().map(blocker::Blocker::new),
+            api: tomato_config.api.as_ref().and_then(api::Api::new),
+            off_hours: false,
+            auto_started_on: None,
+            scale_factor: 1.0,
+            monitors: monitors::monitors(1.0),
+            exist_entity,
+            tomato_config,
+        }
+    }
+
+    fn spawn_clock(&mut self, config: &ClockConfig) -> Task<Message> {
+        let config = self.tomato_config.resolve(config);
+        let position = self.place_clock(&config);
+        let (id, open) = open_clock(&config, position);
+        let scheme_theme = self.tomato_config.scheme_theme(self.color_scheme);
+        let mut clock = TomatoClock::new(
+            self.tomato_config.palette(&config, scheme_theme),
+            config,
+            position,
+        );
+        clock.scheme_theme = scheme_theme.map(ToString::to_string);
+        self.clocks.push((id, clock));
+        open.then(|id| {
+            iced::window::get_scale_factor(id)
+                .map(move |scale_factor| Message::ScaleFactorChanged(id, scale_factor))
+        })
+    }
+
+    /// Where the window of a clock with `config` opens, or `None` to center it.
+    fn place_clock(&self, config: &ClockConfig) -> Option<iced::Point> {
+        let monitors = &self.monitors;
+        let docked_on = config.anchor.and_then(|anchor| {
+            let monitor = match &config.position_monitor {
+                Some(name) => monitors::named(monitors, name),
+                None => config
+                    .position
+                    .and_then(|[x, y]| monitors::at(monitors, iced::Point::new(x, y)))
+                    .or_else(|| monitors::primary(monitors)),
+            }?;
+            Some((monitor, anchor))
+        });
+        match docked_on {
+            Some((monitor, anchor)) => Some(dock::anchored_position(
+                monitor,
+                clock_size(config),
+                anchor,
+                self.tomato_config.snap.unwrap_or_default().margin,
+                config.position.unwrap_or_default(),
+            )),
+            None => monitors::place(
+                monitors,
+                config.position,
+                config.position_monitor.as_deref(),
+            ),
+        }
+    }
+
+    /// Fits the window of a clock to its size, keeping a docked clock on its edge.
+    fn resize_clock(&self, id: iced::window::Id) -> Task<Message> {
+        let Some((_, clock)) = self.clocks.iter().find(|(clock_id, _)| *clock_id == id) else {
+            return Task::none();
+        };
+        let size = clock.window_size();
+        let resize = iced::window::resize(id, size);
+        // A docked clock grows away from its edge instead of past it.
+        let (Some(anchor), Some(position)) = (clock.config.anchor, clock.position) else {
+            return resize;
+        };
+        let Some(monitor) = monitors::at(&self.monitors, position) else {
+            return resize;
+        };
+        let relative = [
+            position.x - monitor.position.x,
+            position.y - monitor.position.y,
+        ];
+        let margin = self.tomato_config.snap.unwrap_or_default().margin;
+        Task::batch([
+            resize,
+            iced::window::move_to(
+                id,
+                dock::anchored_position(monitor, size, anchor, margin, relative),
+            ),
+        ])
+    }
+
+    fn refresh_monitors(&mut self) {
+        self.monitors = monitors::monitors(self.scale_factor);
+    }
+
+    /// The palette behind the colors set in the config file.
+    fn base_palette(&self) -> iced::theme::Palette {
+        match self.color_scheme {
+            Some(appearance::ColorScheme::Dark) => Theme::Dark.palette(),
+            _ => Theme::Light.palette(),
+        }
+    }
+
+    fn clock_mut(&mut self, id: iced::window::Id) -> Option<&mut TomatoClock> {
+        self.clocks
+            .iter_mut()
+            .find(|(clock_id, _)| *clock_id == id)
+            .map(|(_, clock)| clock)
+    }
+
+    /// The next reminder of a clock, with its text and image picked from the configured ones.
+    fn next_reminder(&mut self, clock_id: iced::window::Id) -> Option<Reminder> {
+        let index = self.clocks.iter().position(|(id, _)| *id == clock_id)?;
+        let (_, clock) = &mut self.clocks[index];
+        let config = clock
+            .config
+            .reminder
+            .as_ref()
+            .unwrap_or(&self.tomato_config.reminder);
+        let texts = config.texts();
+        let text = config
+            .pick(texts.len(), clock.reminders_shown)
+            .map(|index| {
+                render_template(
+                    texts[index],
+                    clock.phase,
+                    clock.config.label.as_deref().unwrap_or_default(),
+                )
+            });
+        let text = self
+            .scripts
+            .as_mut()
+            .and_then(|scripts| {
+                scripts.reminder(&scripting::Clock {
+                    index,
+                    running: matches!(clock.state, State::Ticking { .. }),
+                    details: &clock.hook_details(),
+                })
+            })
+            .or(text);
+        let mut reminder = Reminder::new(
+            &text,
+            &config.color,
+            &config.font_size,
+            &config.width,
+            &config.height,
+        );
+        reminder.clock = clock_id;
+        let image_paths = config.image_paths();
+        reminder.picture = config
+            .pick(image_paths.len(), clock.reminders_shown)
+            .map(|index| media::Source {
+                path: image_paths[index].to_string(),
+                width: config.width,
+                height: config.height,
+            });
+        reminder.caption = config.caption.unwrap_or_default();
+        if let Some(color) = clock.palette.reminder {
+            reminder.color = color;
+        }
+        clock.reminders_shown += 1;
+        Some(reminder)
+    }
+
+    /// Opens the windows of `reminder` on the monitors the reminder config of `clock_id` names.
+    fn show_reminder(&mut self, clock_id: iced::window::Id, reminder: Reminder) -> Task<Message> {
+        let load = self.load_picture(&reminder);
+        let Some((_, clock)) = self.clocks.iter().find(|(id, _)| *id == clock_id) else {
+            return load;
+        };
+        let monitors = &self.monitors;
+        let targets = match clock
+            .config
+            .reminder
+            .as_ref()
+            .unwrap_or(&self.tomato_config.reminder)
+            .monitor
+            .as_deref()
+        {
+            None => vec![None],
+            Some("all") if !monitors.is_empty() => monitors.iter().map(Some).collect(),
+            Some("all") => vec![None],
+            Some("clock") => {
+                vec![clock
+                    .position
+                    .and_then(|point| monitors::at(monitors, point))]
             }
-        } else {
-            None
+            Some(name) => vec![monitors::named(monitors, name)],
         };
-        let (id, open) = iced::window::open(iced::window::Settings {
-            size: iced::Size::new(150f32, 45f32),
-            position: if let Some(position) = tomato_config.position {
-                iced::window::Position::Specific(iced::Point::new(position[0], position[1]))
-            } else {
-                iced::window::Position::Centered
-            },
+        let mut opens = vec![load];
+        for monitor in targets {
+            let (id, open) = iced::window::open(iced::window::Settings {
+                position: monitor.map_or(iced::window::Position::Centered, |monitor| {
+                    iced::window::Position::Specific(monitor.position)
+                }),
+                size: monitor.map_or(iced::window::Settings::default().size, |monitor| {
+                    monitor.size
+                }),
+                resizable: false,
+                decorations: false,
+                transparent: true,
+                level: iced::window::Level::AlwaysOnTop,
+                ..Default::default()
+            });
+            self.reminders.push((id, reminder.clone()));
+            opens.push(open.then(|id| iced::window::maximize(id, true)));
+        }
+        Task::batch(opens)
+    }
+
+    /// Decodes the picture of `reminder` in the background unless it is already cached.
+    fn load_picture(&mut self, reminder: &Reminder) -> Task<Message> {
+        match reminder
+            .picture
+            .as_ref()
+            .and_then(|source| self.media.request(source))
+        {
+            Some(loaded) => Task::perform(loaded, |_| Message::PictureLoaded),
+            None => Task::none(),
+        }
+    }
+
+    /// Covers every monitor with a fullscreen overlay until the break of `clock_id` is over.
+    fn open_break_overlays(&mut self, clock_id: iced::window::Id) -> Task<Message> {
+        let Some(reminder) = self.next_reminder(clock_id) else {
+            return Task::none();
+        };
+        let load = self.load_picture(&reminder);
+        let settings = |position, size| iced::window::Settings {
+            size,
+            position,
             resizable: false,
             decorations: false,
             transparent: true,
             level: iced::window::Level::AlwaysOnTop,
-            icon: Some(iced::window::Icon::from(
-                iced::window::icon::from_file_data(include_bytes!("..\\tomato.ico"), None).unwrap(),
-            )),
+            exit_on_close_request: false,
             ..Default::default()
+        };
+        let monitors = &self.monitors;
+        let windows = if monitors.is_empty() {
+            vec![settings(
+                iced::window::Position::Centered,
+                iced::window::Settings::default().size,
+            )]
+        } else {
+            monitors
+                .iter()
+                .map(|monitor| {
+                    settings(
+                        iced::window::Position::Specific(monitor.position),
+                        monitor.size,
+                    )
+                })
+                .collect()
+        };
+        let mut opens = vec![load];
+        opens.extend(windows.into_iter().map(|settings| {
+            let (id, open) = iced::window::open(settings);
+            self.break_overlays.push((
+                id,
+                BreakOverlay {
+                    clock: clock_id,
+                    reminder: reminder.clone(),
+                },
+            ));
+            open.then(|id| iced::window::change_mode(id, iced::window::Mode::Fullscreen))
+        }));
+        Task::batch(opens)
+    }
+
+    fn close_break_overlays(&mut self, clock_id: iced::window::Id) -> Task<Message> {
+        let mut closes = Vec::new();
+        self.break_overlays.retain(|(id, overlay)| {
+            if overlay.clock == clock_id {
+                closes.push(iced::window::close(*id));
+            }
+            overlay.clock != clock_id
         });
-        tomato_config.run_background_color_index =
-            if tomato_config.run_background_color_index < tomato_config.background_colors.len() {
-                tomato_config.run_background_color_index
-            } else {
-                tomato_config.background_colors.len() - 1
-            };
-        tomato_config.stop_background_color_index =
-            if tomato_config.stop_background_color_index < tomato_config.background_colors.len() {
-                tomato_config.stop_background_color_index
-            } else {
-                tomato_config.background_colors.len() - 1
-            };
-        tomato_config.run_text_color_index =
-            if tomato_config.run_text_color_index < tomato_config.text_colors.len() {
-                tomato_config.run_text_color_index
-            } else {
-                tomato_config.text_colors.len() - 1
-            };
-        tomato_config.stop_text_color_index =
-            if tomato_config.stop_text_color_index < tomato_config.text_colors.len() {
-                tomato_config.stop_text_color_index
-            } else {
-                tomato_config.text_colors.len() - 1
-            };
+        Task::batch(closes)
+    }
 
-        (
-            Self {
-                windows: (
-                    (
-                        id,
-                        TomatoClock::new(
-                            tomato_config.background_colors
-                                [tomato_config.run_background_color_index]
-                                .into(),
-                            tomato_config.background_colors
-                                [tomato_config.stop_background_color_index]
-                                .into(),
-                            tomato_config.text_colors[tomato_config.run_text_color_index].into(),
-                            tomato_config.text_colors[tomato_config.stop_text_color_index].into(),
-                        ),
-                    ),
-                    None,
-                ),
-                picture_data,
-                exist_entity,
-                tomato_config,
-            },
-            open.then(|_| Task::none()),
-        )
+    /// Closes the overlays of the clocks whose break is over, however it ended.
+    fn close_ended_break_overlays(&mut self) -> Task<Message> {
+        let ended = self
+            .break_overlays
+            .iter()
+            .map(|(_, overlay)| overlay.clock)
+            .filter(|clock_id| {
+                !self
+                    .clocks
+                    .iter()
+                    .any(|(id, clock)| id == clock_id && clock.phase == Phase::Break)
+            })
+            .collect::<Vec<_>>();
+        let closes = ended
+            .into_iter()
+            .map(|clock_id| self.close_break_overlays(clock_id))
+            .collect::<Vec<_>>();
+        Task::batch(closes)
     }
+
     fn title(&self, window: iced::window::Id) -> String {
-        if self.windows.0 .0 == window {
-            "Tomato Clock".to_string()
+        if let Some((_, clock)) = self.clocks.iter().find(|(id, _)| *id == window) {
+            clock
+                .config
+                .label
+                .clone()
+                .unwrap_or_else(|| "Tomato Clock".to_string())
+        } else if self.break_overlays.iter().any(|(id, _)| *id == window) {
+            "Break".to_string()
         } else {
             "Time out".to_string()
         }
     }
 
-    fn view(&self, window: iced::window::Id) -> Element<Message> {
-        if self.windows.0 .0 == window {
-            self.windows.0 .1.view()
+    fn view(&self, window: iced::window::Id) -> Element<'_, Message> {
+        if let Some((id, clock)) = self.clocks.iter().find(|(id, _)| *id == window) {
+            clock.view(*id)
+        } else if let Some((id, reminder)) = self.reminders.iter().find(|(id, _)| *id == window) {
+            reminder.view(*id, self.media.status(reminder.picture.as_ref()))
+        } else if let Some((id, overlay)) = self.break_overlays.iter().find(|(id, _)| *id == window)
+        {
+            let Some((_, clock)) = self.clocks.iter().find(|(id, _)| *id == overlay.clock) else {
+                return iced::widget::horizontal_space().into();
+            };
+            let skips_left = self
+                .tomato_config
+                .strict_break
+                .as_ref()
+                .map_or(0, |strict| {
+                    if self.skipped_breaks.0 == today() {
+                        strict.skips_per_day.saturating_sub(self.skipped_breaks.1)
+                    } else {
+                        strict.skips_per_day
+                    }
+                });
+            let can_postpone = self
+                .tomato_config
+                .strict_break
+                .as_ref()
+                .is_some_and(|strict| {
+                    strict.postpone_minutes.is_some()
+                        && clock.postpones < strict.max_postpones.unwrap_or(1)
+                });
+            overlay.view(
+                *id,
+                clock,
+                self.media.status(overlay.reminder.picture.as_ref()),
+                skips_left,
+                can_postpone,
+            )
         } else {
-            if let Some((_, reminder)) = &self.windows.1 {
-                reminder.view(self.picture_data.as_ref())
-            } else {
-                iced::widget::horizontal_space().into()
-            }
+            iced::widget::horizontal_space().into()
         }
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
-        match message {
-            Message::TimeOut => {
-                let (id, open) = iced::window::open(iced::window::Settings {
-                    position: iced::window::Position::Centered,
-                    resizable: false,
-                    decorations: false,
-                    transparent: true,
-                    level: iced::window::Level::AlwaysOnTop,
-                    ..Default::default()
-                });
-                let ReminderConfig {
-                    text,
-                    color,
-                    font_size,
-                    width,
-                    height,
-                    ..
-                } = &self.tomato_config.reminder;
-                let reminder = Reminder::new(text, color, font_size, width, height);
-                self.windows.1 = Some((id, reminder));
-                return open.then(|id| iced::window::maximize(id, true));
-            }
-            Message::StartDragging => {
-                return iced::window::drag(self.windows.0 .0);
-            }
-            Message::CloseReminder => {
-                if let Some((id, _)) = self.windows.1 {
-                    self.windows.1 = None;
-                    return iced::window::close(id);
-                }
-            }
-            Message::ChangeTextColor => {
-                if let State::Idle = self.windows.0 .1.state {
-                    self.tomato_config.stop_text_color_index =
-                        (self.tomato_config.stop_text_color_index + 1)
-                            % self.tomato_config.text_colors.len();
-                    self.windows.0 .1.stop_text_color = self.tomato_config.text_colors
-                        [self.tomato_config.stop_text_color_index]
-                        .into();
-                } else {
-                    self.tomato_config.run_text_color_index =
-                        (self.tomato_config.run_text_color_index + 1)
-                            % self.tomato_config.text_colors.len();
-                    self.windows.0 .1.run_text_color = self.tomato_config.text_colors
-                        [self.tomato_config.run_text_color_index]
-                        .into();
-                }
-            }
-            Message::ChangeBackgroundColor => {
-                if let State::Idle = self.windows.0 .1.state {
-                    self.tomato_config.stop_background_color_index =
-                        (self.tomato_config.stop_background_color_index + 1)
-                            % self.tomato_config.background_colors.len();
-                    self.windows.0 .1.stop_background_color = self.tomato_config.background_colors
-                        [self.tomato_config.stop_background_color_index]
-                        .into();
-                } else {
-                    self.tomato_config.run_background_color_index =
-                        (self.tomato_config.run_background_color_index + 1)
-                            % self.tomato_config.background_colors.len();
-                    self.windows.0 .1.run_background_color = self.tomato_config.background_colors
-                        [self.tomato_config.run_background_color_index]
-                        .into();
+        let task = self.handle(message);
+        let events = self.run_hooks();
+        let mut happened = events
+            .iter()
+            .map(|(id, event, _)| (*id, *event))
+            .collect::<Vec<_>>();
+        let scripted = self.run_scripts(message, events);
+        // Events caused by the scripts' actions only run hooks, so scripts cannot loop.
+        happened.extend(
+            self.run_hooks()
+                .into_iter()
+                .map(|(id, event, _)| (id, event)),
+        );
+        let closes = self.close_ended_break_overlays();
+        if let Some(api) = &self.api {
+            let index = |id| self.clocks.iter().position(|(clock_id, _)| *clock_id == id);
+            let events = happened
+                .into_iter()
+                .filter_map(|(id, event)| Some((index(id)?, event.name())))
+                .collect::<Vec<_>>();
+            let clocks = self
+                .clocks
+                .iter()
+                .enumerate()
+                .map(|(index, (_, clock))| clock.api_status(index))
+                .collect();
+            api.publish(clocks, &events);
+        }
+        // Stopwatches and countdowns to a time of day time other things than focus.
+        let focusing = self.clocks.iter().any(|(_, clock)| {
+            matches!(clock.state, State::Ticking { .. })
+                && matches!(clock.mode, Mode::Pomodoro)
+                && clock.phase == Phase::Focus
+        });
+        if let Some(do_not_disturb) = &mut self.do_not_disturb {
+            do_not_disturb.set(focusing);
+        }
+        if let Some(blocker) = &self.blocker {
+            blocker.set(focusing);
+        }
+        Task::batch([task, scripted, closes])
+    }
+
+    /// Starts the hook commands of the events the clocks reported and lets the media players
+    /// follow them, and returns the events.
+    fn run_hooks(&mut self) -> Vec<(iced::window::Id, hooks::Event, hooks::Details)> {
+        let mut events = Vec::new();
+        for (id, clock) in &mut self.clocks {
+            for (event, details) in clock.hook_events.drain(..) {
+                if let Some(hooks) = &self.tomato_config.hooks {
+                    // Left to finish on its own thread.
+                    drop(hooks.run(event, &details));
                 }
+                if let Some(music) = &self.music {
+                    music.follow(event, &details);
+                }
+                events.push((*id, event, details));
             }
-            Message::Shutdown => {
-                if self.exist_entity {
-                    let mut tomato_config = self.tomato_config.clone();
-                    return iced::window::get_position(self.windows.0 .0).then(move |pos| {
-                        if let Some(iced::Point { x, y }) = pos {
-                            tomato_config.position = Some([x, y]);
-                        }
-                        std::fs::write(CONFIG_PATH, toml::to_string(&tomato_config).unwrap())
-                            .expect("Failed to write config file");
-                        iced::exit()
-                    });
-                };
-                return iced::exit();
-            }
-            _ => return self.windows.0 .1.update(message),
         }
-        Task::none()
+        events
     }
 
-    fn theme(&self, window: iced::window::Id) -> Theme {
-        if self.windows.0 .0 == window {
-            self.windows.0 .1.theme()
-        } else {
-            Theme::custom(
-                "reminder".to_string(),
-                iced::theme::Palette {
-                    background: iced::Color::TRANSPARENT,
-                    ..Theme::default().palette()
-                },
-            )
+    /// Passes the message and the clock events to the scripts and carries out their actions.
+    fn run_scripts(
+        &mut self,
+        message: Message,
+        events: Vec<(iced::window::Id, hooks::Event, hooks::Details)>,
+    ) -> Task<Message> {
+        let Some(scripts) = &mut self.scripts else {
+            return Task::none();
+        };
+        let clock = |clocks: &[(iced::window::Id, TomatoClock)], id| {
+            let index = clocks.iter().position(|(clock_id, _)| *clock_id == id)?;
+            let (_, clock) = &clocks[index];
+            Some((
+                index,
+                matches!(clock.state, State::Ticking { .. }),
+                clock.hook_details(),
+            ))
+        };
+        let mut actions = Vec::new();
+        let target = Some(message)
+            .filter(Message::reaches_scripts)
+            .and_then(|message| {
+                message
+                    .window()
+                    .filter(|id| self.clocks.iter().any(|(clock_id, _)| clock_id == id))
+                    .or_else(|| self.clocks.first().map(|(id, _)| *id))
+            });
+        if let Some((id, (index, running, details))) =
+            target.and_then(|id| Some((id, clock(&self.clocks, id)?)))
+        {
+            let name = message.name();
+            let clock = scripting::Clock {
+                index,
+                running,
+                details: &details,
+            };
+            actions.extend(
+                scripts
+                    .on_message(name, &clock)
+                    .into_iter()
+                    .map(|action| (id, action)),
+            );
         }
+        for (id, event, details) in events {
+            let Some((index, running, _)) = clock(&self.clocks, id) else {
+                continue;
+            };
+            let clock = scripting::Clock {
+                index,
+                running,
+                details: &details,
+            };
+            actions.extend(
+                scripts
+                    .on_event(event, &clock)
+                    .into_iter()
+                    .map(|action| (id, action)),
+            );
+        }
+        let tasks = actions
+            .into_iter()
+            .map(|(id, action)| self.apply_script_action(id, action))
+            .collect::<Vec<_>>();
+        self.report_script_errors();
+        Task::batch(tasks)
     }
-    fn subscription(&self) -> Subscription<Message> {
-        self.windows.0 .1.subscription()
-    }
-}
 
-impl TomatoClock {
-    fn new(
-        run_background_color: iced::Color,
-        stop_background_color: iced::Color,
-        run_text_color: iced::Color,
-        stop_text_color: iced::Color,
-    ) -> Self {
-        Self {
-            duration: Duration::from_secs(25 * 60),
-            state: State::default(),
-            mode: Mode::default(),
-            pomodoro_duration: Duration::from_secs(25 * 60),
-            run_background_color,
-            run_text_color,
-            stop_background_color,
-            stop_text_color,
+    fn apply_script_action(
+        &mut self,
+        id: iced::window::Id,
+        action: scripting::Action,
+    ) -> Task<Message> {
+        let Some(clock) = self.clock_mut(id) else {
+            return Task::none();
+        };
+        let running = matches!(clock.state, State::Ticking { .. });
+        // Handled right away rather than sent as messages, which would reach the scripts again.
+        match action {
+            scripting::Action::Start if !running => return self.handle(Message::Toggle(id)),
+            scripting::Action::Pause if running => return self.handle(Message::Toggle(id)),
+            scripting::Action::Start | scripting::Action::Pause => {}
+            scripting::Action::Reset => return self.handle(Message::Reset(id)),
+            scripting::Action::SkipBreak => {
+                if clock.phase == Phase::Break {
+                    clock.skip_break();
+                    return self.close_break_overlays(id);
+                }
+            }
+            scripting::Action::SetPomodoroMinutes(minutes) => {
+                clock.pomodoro_duration = Duration::from_secs(minutes * 60);
+                if matches!(clock.mode, Mode::Pomodoro)
+                    && clock.phase == Phase::Focus
+                    && clock.session.is_none()
+                {
+                    clock.duration = clock.pomodoro_duration;
+                }
+            }
+            scripting::Action::SetBreakMinutes(minutes) => {
+                clock.break_duration = (minutes > 0).then(|| Duration::from_secs(minutes * 60));
+            }
+            scripting::Action::Remind(text) => {
+                if self.off_hours {
+                    return Task::none();
+                }
+                let Some(mut reminder) = self.next_reminder(id) else {
+                    return Task::none();
+                };
+                reminder.text = text;
+                reminder.caption = true;
+                return self.show_reminder(id, reminder);
+            }
         }
+        Task::none()
     }
 
-    fn update(&mut self, message: Message) -> Task<Message> {
+    /// Prints the script errors and shows the last one on the main clock.
+    fn report_script_errors(&mut self) {
+        let Some(scripts) = &mut self.scripts else {
+            return;
+        };
+        let errors = scripts.take_errors();
+        for error in &errors {
+            eprintln!("Script error: {error}");
+        }
+        if let (Some(error), Some((_, clock))) = (errors.last(), self.clocks.first_mut()) {
+            clock.script_error = Some(error.clone());
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> Task<Message> {
         match message {
-            Message::Toggle => match self.state {
+            Message::TimeOut(clock_id) => {
+                let close_overlays = self.close_break_overlays(clock_id);
+                if self.off_hours {
+                    return close_overlays;
+                }
+                let Some((_, clock)) = self.clocks.iter().find(|(id, _)| *id == clock_id) else {
+                    return close_overlays;
+                };
+                if clock.phase == Phase::Break && self.tomato_config.strict_break.is_some() {
+                    return Task::batch([close_overlays, self.open_break_overlays(clock_id)]);
+                }
+                let Some(reminder) = self.next_reminder(clock_id) else {
+                    return close_overlays;
+                };
+                return Task::batch([close_overlays, self.show_reminder(clock_id, reminder)]);
+            }
+            Message::Api(command) => {
+                let Some((id, clock)) = self.clocks.get(command.clock) else {
+                    return Task::none();
+                };
+                let id = *id;
+                let running = matches!(clock.state, State::Ticking { .. });
+                let message = match command.action {
+                    api::Action::Toggle => Message::Toggle(id),
+                    api::Action::Start if !running => Message::Toggle(id),
+                    api::Action::Pause if running => Message::Toggle(id),
+                    api::Action::Start | api::Action::Pause => return Task::none(),
+                    api::Action::Reset => Message::Reset(id),
+                    api::Action::ToggleMode => Message::ToggleMode(id),
+                    api::Action::SkipBreak => Message::SkipBreak(id),
+                    api::Action::PostponeBreak => Message::PostponeBreak(id),
+                    api::Action::Lap => Message::Lap(id),
+                    api::Action::Longer => Message::IncreasePomodoroDuration(id),
+                    api::Action::Shorter => Message::DecreasePomodoroDuration(id),
+                };
+                return Task::done(message);
+            }
+            Message::GoalReached => {
+                let Some(goal) = self.goal.as_ref().filter(|_| !self.off_hours) else {
+                    return Task::none();
+                };
+                let text = goal.celebration();
+                // Celebrate in the reminder of the pomodoro that reached the goal, if it shows.
+                if !self.reminders.is_empty() {
+                    for (_, reminder) in &mut self.reminders {
+                        reminder.text = text.clone();
+                        reminder.caption = true;
+                    }
+                    return Task::none();
+                }
+                let Some(&(clock_id, _)) = self.clocks.first() else {
+                    return Task::none();
+                };
+                let Some(mut reminder) = self.next_reminder(clock_id) else {
+                    return Task::none();
+                };
+                reminder.text = text;
+                reminder.caption = true;
+                return self.show_reminder(clock_id, reminder);
+            }
+            Message::PictureLoaded => {
+                self.media.collect();
+            }
+            // Only redraws the animated pictures.
+            Message::Animate => {}
+            Message::StartDragging(id) => {
+                return iced::window::drag(id);
+            }
+            Message::Moved(id, position) => {
+                if let Some(clock) = self.clock_mut(id) {
+                    clock.position = Some(position);
+                    clock.last_moved = Some(Instant::now());
+                }
+            }
+            Message::ScaleFactorChanged(_, scale_factor) => {
+                if scale_factor == self.scale_factor {
+                    return Task::none();
+                }
+                self.scale_factor = scale_factor;
+                self.refresh_monitors();
+                // The clocks were placed before the scale factor was known.
+                let mut moves = Vec::new();
+                for index in 0..self.clocks.len() {
+                    let (id, clock) = &self.clocks[index];
+                    let (id, Some(position)) = (*id, self.place_clock(&clock.config)) else {
+                        continue;
+                    };
+                    self.clocks[index].1.position = Some(position);
+                    moves.push(iced::window::move_to(id, position));
+                }
+                return Task::batch(moves);
+            }
+            Message::SettleDrag => {
+                let snap = self.tomato_config.snap;
+                let margin = snap.unwrap_or_default().margin;
+                // Asks the X server for the monitors only once a drag is over.
+                let settled = self.clocks.iter().any(|(_, clock)| {
+                    clock
+                        .last_moved
+                        .is_some_and(|last_moved| last_moved.elapsed() >= SETTLE_DELAY)
+                });
+                if !settled {
+                    return Task::none();
+                }
+                self.refresh_monitors();
+                let mut moves = Vec::new();
+                for (id, clock) in &mut self.clocks {
+                    let (Some(last_moved), Some(position)) = (clock.last_moved, clock.position)
+                    else {
+                        continue;
+                    };
+                    if last_moved.elapsed() < SETTLE_DELAY {
+                        continue;
+                    }
+                    clock.last_moved = None;
+                    let Some(monitor) = monitors::at(&self.monitors, position) else {
+                        continue;
+                    };
+                    let size = clock.window_size();
+                    let relative = [
+                        position.x - monitor.position.x,
+                        position.y - monitor.position.y,
+                    ];
+                    let anchor = match snap {
+                        Some(snap) => dock::snap(monitor, position, size, snap.distance),
+                        // Without snapping, a docked clock only stays docked until it is dragged.
+                        None => clock.config.anchor.filter(|anchor| {
+                            dock::anchored_position(monitor, size, *anchor, margin, relative)
+                                .distance(position)
+                                < 1.0
+                        }),
+                    };
+                    clock.config.anchor = anchor;
+                    if let Some(anchor) = anchor {
+                        let target =
+                            dock::anchored_position(monitor, size, anchor, margin, relative);
+                        if target.distance(position) >= 1.0 {
+                            moves.push(iced::window::move_to(*id, target));
+                        }
+                    }
+                }
+                return Task::batch(moves);
+            }
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+            }
+            Message::Scrolled(id, lines) => {
+                if !self.modifiers.control() {
+                    return Task::none();
+                }
+                let Some(clock) = self.clock_mut(id) else {
+                    return Task::none();
+                };
+                let scale = clock.config.scale.unwrap_or(1.0);
+                clock.config.scale = Some((scale + lines * 0.1).clamp(0.5, 4.0));
+                return self.resize_clock(id);
+            }
+            Message::ToggleLaps(id) => {
+                let Some(clock) = self.clock_mut(id) else {
+                    return Task::none();
+                };
+                clock.show_laps = !clock.show_laps;
+                return self.resize_clock(id);
+            }
+            Message::Dock(id, direction) => {
+                let margin = self.tomato_config.snap.unwrap_or_default().margin;
+                let monitors = &self.monitors;
+                let Some((_, clock)) = self.clocks.iter_mut().find(|(clock_id, _)| *clock_id == id)
+                else {
+                    return Task::none();
+                };
+                let Some(position) = clock.position else {
+                    return Task::none();
+                };
+                let Some(monitor) =
+                    monitors::at(monitors, position).or_else(|| monitors::primary(monitors))
+                else {
+                    return Task::none();
+                };
+                let size = clock.window_size();
+                let anchor =
+                    dock::Anchor::towards(direction, clock.config.anchor, monitor, position, size);
+                clock.config.anchor = Some(anchor);
+                let relative = [
+                    position.x - monitor.position.x,
+                    position.y - monitor.position.y,
+                ];
+                return iced::window::move_to(
+                    id,
+                    dock::anchored_position(monitor, size, anchor, margin, relative),
+                );
+            }
+            Message::ColorSchemeChanged(scheme) => {
+                self.color_scheme = Some(scheme);
+                let theme = self.tomato_config.scheme_theme(self.color_scheme);
+                for (_, clock) in &mut self.clocks {
+                    clock.follow_scheme(&self.tomato_config, theme);
+                }
+                // Reminders that are already open switch along with their clock.
+                let clocks = &self.clocks;
+                let reminders = self
+                    .reminders
+                    .iter_mut()
+                    .map(|(_, reminder)| reminder)
+                    .chain(
+                        self.break_overlays
+                            .iter_mut()
+                            .map(|(_, overlay)| &mut overlay.reminder),
+                    );
+                for reminder in reminders {
+                    if let Some(color) = clocks
+                        .iter()
+                        .find(|(id, _)| *id == reminder.clock)
+                        .and_then(|(_, clock)| clock.palette.reminder)
+                    {
+                        reminder.color = color;
+                    }
+                }
+            }
+            Message::CloseReminder(id) => {
+                // A reminder shown on several monitors is dismissed everywhere at once.
+                if let Some((_, reminder)) = self.reminders.iter().find(|(rid, _)| *rid == id) {
+                    let clock = reminder.clock;
+                    let mut closes = Vec::new();
+                    self.reminders.retain(|(rid, reminder)| {
+                        if reminder.clock == clock {
+                            closes.push(iced::window::close(*rid));
+                        }
+                        reminder.clock != clock
+                    });
+                    return Task::batch(closes);
+                }
+            }
+            Message::NewClock => {
+                let config = ClockConfig::default();
+                return self.spawn_clock(&config);
+            }
+            Message::CloseClock(id) => {
+                // Escape in a reminder or on a break overlay closes nothing.
+                let Some(index) = self.clocks.iter().position(|(cid, _)| *cid == id) else {
+                    return Task::none();
+                };
+                if index == 0 {
+                    return Task::done(Message::Shutdown);
+                }
+                let (_, mut clock) = self.clocks.remove(index);
+                clock.finish_session(false);
+                let mut closes = vec![iced::window::close(id), self.close_break_overlays(id)];
+                self.reminders.retain(|(rid, reminder)| {
+                    if reminder.clock == id {
+                        closes.push(iced::window::close(*rid));
+                    }
+                    reminder.clock != id
+                });
+                return self.track_goal(Task::batch(closes));
+            }
+            Message::ChangeTextColor(id) => {
+                let tomato_config = &self.tomato_config;
+                let len = tomato_config.text_colors.len();
+                let Some((_, clock)) = self.clocks.iter_mut().find(|(cid, _)| *cid == id) else {
+                    return Task::none();
+                };
+                if let State::Idle = clock.state {
+                    let index = (clock.config.stop_text_color_index.unwrap_or_default() + 1) % len;
+                    clock.config.stop_text_color_index = Some(index);
+                } else {
+                    let index = (clock.config.run_text_color_index.unwrap_or_default() + 1) % len;
+                    clock.config.run_text_color_index = Some(index);
+                }
+                clock.palette = tomato_config.palette(&clock.config, clock.scheme_theme.as_deref());
+            }
+            Message::ChangeBackgroundColor(id) => {
+                let tomato_config = &self.tomato_config;
+                let len = tomato_config.background_colors.len();
+                let Some((_, clock)) = self.clocks.iter_mut().find(|(cid, _)| *cid == id) else {
+                    return Task::none();
+                };
+                if let State::Idle = clock.state {
+                    let index =
+                        (clock.config.stop_background_color_index.unwrap_or_default() + 1) % len;
+                    clock.config.stop_background_color_index = Some(index);
+                } else {
+                    let index =
+                        (clock.config.run_background_color_index.unwrap_or_default() + 1) % len;
+                    clock.config.run_background_color_index = Some(index);
+                }
+                clock.palette = tomato_config.palette(&clock.config, clock.scheme_theme.as_deref());
+            }
+            Message::CycleTheme(id) => {
+                let tomato_config = &self.tomato_config;
+                let Some((_, clock)) = self.clocks.iter_mut().find(|(cid, _)| *cid == id) else {
+                    return Task::none();
+                };
+                // Cycles through the themes and back to the plain color indices.
+                let names = tomato_config.theme_names();
+                // A theme picked here replaces the one of the color scheme until it changes.
+                let current = clock.scheme_theme.take().or(clock.config.theme.clone());
+                let next = match current
+                    .as_deref()
+                    .and_then(|current| names.iter().position(|name| *name == current))
+                {
+                    Some(index) => names.get(index + 1),
+                    None => names.first(),
+                };
+                clock.config.theme = next.map(ToString::to_string);
+                clock.palette = tomato_config.palette(&clock.config, None);
+            }
+            Message::Shutdown => {
+                for (_, clock) in &mut self.clocks {
+                    clock.finish_session(false);
+                }
+                if self.exist_entity {
+                    let mut tomato_config = self.tomato_config.clone();
+                    let mut clocks = self
+                        .clocks
+                        .iter()
+                        .map(|(_, clock)| clock.saved_config(&self.monitors));
+                    if let Some(main) = clocks.next() {
+                        tomato_config.position = main.position;
+                        tomato_config.position_monitor = main.position_monitor;
+                        tomato_config.anchor = main.anchor;
+                        tomato_config.scale = main.scale;
+                        tomato_config.theme = main.theme;
+                        tomato_config.stop_text_color_index =
+                            main.stop_text_color_index.unwrap_or_default();
+                        tomato_config.run_text_color_index =
+                            main.run_text_color_index.unwrap_or_default();
+                        tomato_config.stop_background_color_index =
+                            main.stop_background_color_index.unwrap_or_default();
+                        tomato_config.run_background_color_index =
+                            main.run_background_color_index.unwrap_or_default();
+                    }
+                    tomato_config.clocks = clocks.collect();
+                    std::fs::write(CONFIG_PATH, toml::to_string(&tomato_config).unwrap())
+                        .expect("Failed to write config file");
+                };
+                if let Some(do_not_disturb) = &mut self.do_not_disturb {
+                    do_not_disturb.restore();
+                }
+                // Give the shutdown hook the chance to finish before exiting.
+                let shutdown = self.tomato_config.hooks.as_ref().and_then(|hooks| {
+                    let (_, clock) = self.clocks.first()?;
+                    hooks.run(hooks::Event::Shutdown, &clock.hook_details())
+                });
+                return match shutdown {
+                    Some(finished) => Task::future(finished).then(|()| iced::exit()),
+                    None => iced::exit(),
+                };
+            }
+            Message::CheckCalendar => self.follow_calendar(),
+            Message::CheckSchedule => return self.follow_schedule(),
+            Message::CheckIdle => {
+                let Some(monitor) = &mut self.idle_monitor else {
+                    return Task::none();
+                };
+                if let Some(idle) = monitor.away() {
+                    for (_, clock) in &mut self.clocks {
+                        clock.pause_for_idle(idle);
+                    }
+                }
+            }
+            Message::SkipBreak(id) | Message::PostponeBreak(id) => {
+                let Some(strict_break) = &self.tomato_config.strict_break else {
+                    return Task::none();
+                };
+                let skips_per_day = strict_break.skips_per_day;
+                let postpone_minutes = strict_break.postpone_minutes;
+                let max_postpones = strict_break.max_postpones.unwrap_or(1);
+                let clock_id = self
+                    .break_overlays
+                    .iter()
+                    .find(|(overlay_id, _)| *overlay_id == id)
+                    .map_or(id, |(_, overlay)| overlay.clock);
+                if self.skipped_breaks.0 != today() {
+                    self.skipped_breaks = (today(), 0);
+                }
+                let skipped = self.skipped_breaks.1;
+                let Some(clock) = self.clock_mut(clock_id) else {
+                    return Task::none();
+                };
+                if clock.phase != Phase::Break {
+                    return Task::none();
+                }
+                match (message, postpone_minutes) {
+                    (Message::SkipBreak(_), _) if skipped < skips_per_day => {
+                        clock.skip_break();
+                        self.skipped_breaks.1 += 1;
+                        history::append_skipped_break();
+                    }
+                    (Message::PostponeBreak(_), Some(minutes))
+                        if clock.postpones < max_postpones =>
+                    {
+                        clock.postpone_break(Duration::from_secs(minutes * 60));
+                    }
+                    _ => return Task::none(),
+                }
+                return self.close_break_overlays(clock_id);
+            }
+            Message::Tick(_) => {
+                let ticks = Task::batch(
+                    self.clocks
+                        .iter_mut()
+                        .map(|(id, clock)| clock.update(*id, message)),
+                );
+                return self.track_goal(ticks);
+            }
+            Message::Toggle(id)
+            | Message::ToggleMode(id)
+            | Message::Reset(id)
+            | Message::IncreasePomodoroDuration(id)
+            | Message::DecreasePomodoroDuration(id)
+            | Message::EarlyTermination(id)
+            | Message::KeepIdle(id)
+            | Message::DiscardIdle(id)
+            | Message::TypeDigit(id, _)
+            | Message::EraseDigit(id)
+            | Message::FitToCalendar(id)
+            | Message::Lap(id) => {
+                let strict = self.tomato_config.strict_break.is_some();
+                let Some(clock) = self.clock_mut(id) else {
+                    return Task::none();
+                };
+                // A strict break only ends early by skipping or postponing it.
+                if strict
+                    && clock.phase == Phase::Break
+                    && matches!(
+                        message,
+                        Message::Reset(_) | Message::ToggleMode(_) | Message::EarlyTermination(_)
+                    )
+                {
+                    return Task::none();
+                }
+                clock.script_error = None;
+                let task = clock.update(id, message);
+                self.follow_calendar();
+                return self.track_goal(task);
+            }
+        }
+        Task::none()
+    }
+
+    /// Updates the goal progress on the main clock once sessions were written to the history,
+    /// and celebrates after `task` once the goal is reached.
+    fn track_goal(&mut self, task: Task<Message>) -> Task<Message> {
+        let Some(goal) = &mut self.goal else {
+            return task;
+        };
+        let reached = goal.refresh();
+        if let Some((_, clock)) = self.clocks.first_mut() {
+            clock.progress = Some(goal.progress());
+        }
+        if reached {
+            // Chained, so that the reminder of the pomodoro that reached the goal opens first.
+            task.chain(Task::done(Message::GoalReached))
+        } else {
+            task
+        }
+    }
+
+    /// Dims or hides the clocks outside the working hours and starts the first pomodoro of the
+    /// day when it is due.
+    fn follow_schedule(&mut self) -> Task<Message> {
+        let Some(schedule) = &self.schedule else {
+            return Task::none();
+        };
+        let now = chrono::Local::now().naive_local();
+        let off_hours = !schedule.working(now);
+        let mut tasks = Vec::new();
+        for (id, clock) in &mut self.clocks {
+            clock.dimmed = off_hours && schedule.outside == schedule::Outside::Dim;
+            if off_hours != self.off_hours && schedule.outside == schedule::Outside::Hide {
+                let mode = if off_hours {
+                    iced::window::Mode::Hidden
+                } else {
+                    iced::window::Mode::Windowed
+                };
+                tasks.push(iced::window::change_mode(*id, mode));
+            }
+        }
+        self.off_hours = off_hours;
+        if schedule.auto_start_due(now) && self.auto_started_on != Some(now.date()) {
+            self.auto_started_on = Some(now.date());
+            if let Some((id, clock)) = self.clocks.first() {
+                if matches!((clock.mode, &clock.state), (Mode::Pomodoro, State::Idle))
+                    && clock.phase == Phase::Focus
+                    && clock.session.is_none()
+                {
+                    tasks.push(Task::done(Message::Toggle(*id)));
+                }
+            }
+        }
+        Task::batch(tasks)
+    }
+
+    /// Reads the calendar files if they changed and lets every clock make way for the events.
+    fn follow_calendar(&mut self) {
+        let Some(calendar) = &mut self.calendar else {
+            return;
+        };
+        calendar.refresh();
+        let hold = self
+            .tomato_config
+            .calendar
+            .as_ref()
+            .and_then(|config| config.hold_during_events)
+            .unwrap_or(true);
+        let now = chrono::Local::now();
+        for (_, clock) in &mut self.clocks {
+            clock.follow_calendar(calendar.current(now), calendar.next(now), hold, now);
+        }
+    }
+
+    fn theme(&self, window: iced::window::Id) -> Theme {
+        if let Some((_, clock)) = self.clocks.iter().find(|(id, _)| *id == window) {
+            clock.theme(self.base_palette())
+        } else if self.break_overlays.iter().any(|(id, _)| *id == window) {
+            Theme::custom(
+                "break".to_string(),
+                iced::theme::Palette {
+                    background: self
+                        .tomato_config
+                        .strict_break
+                        .as_ref()
+                        .and_then(|strict| strict.background)
+                        .map_or(iced::Color::from_rgba(0.05, 0.05, 0.05, 0.9), Into::into),
+                    ..self.base_palette()
+                },
+            )
+        } else {
+            Theme::custom(
+                "reminder".to_string(),
+                iced::theme::Palette {
+                    background: iced::Color::TRANSPARENT,
+                    ..self.base_palette()
+                },
+            )
+        }
+    }
+    fn subscription(&self) -> Subscription<Message> {
+        let tick = if self.clocks.iter().any(|(_, clock)| clock.animates()) {
+            time::every(Duration::from_millis(100)).map(Message::Tick)
+        } else if self.clocks.iter().any(|(_, clock)| {
+            // An idle `until` clock still counts down to its time of day.
+            matches!(clock.state, State::Ticking { .. }) || matches!(clock.mode, Mode::Until)
+        }) {
+            time::every(Duration::from_millis(1000)).map(Message::Tick) // equal to |instant| Message::Tick(instant),
+        } else {
+            Subscription::none()
+        };
+        let settle_drag = if self
+            .clocks
+            .iter()
+            .any(|(_, clock)| clock.last_moved.is_some())
+        {
+            time::every(Duration::from_millis(100)).map(|_| Message::SettleDrag)
+        } else {
+            Subscription::none()
+        };
+        let check_idle = if self.idle_monitor.is_some()
+            && self.clocks.iter().any(|(_, clock)| {
+                matches!(clock.state, State::Ticking { .. })
+                    && matches!(clock.mode, Mode::Pomodoro)
+                    && clock.phase == Phase::Focus
+            }) {
+            time::every(Duration::from_secs(5)).map(|_| Message::CheckIdle)
+        } else {
+            Subscription::none()
+        };
+        fn handle_hotkey(key: keyboard::Key, id: iced::window::Id) -> Option<Message> {
+            match key.as_ref() {
+                keyboard::Key::Named(keyboard::key::Named::Space | keyboard::key::Named::Enter) => {
+                    Some(Message::Toggle(id))
+                }
+                keyboard::Key::Named(keyboard::key::Named::Backspace) => {
+                    Some(Message::EraseDigit(id))
+                }
+                keyboard::Key::Character(digit)
+                    if digit.len() == 1 && digit.as_bytes()[0].is_ascii_digit() =>
+                {
+                    Some(Message::TypeDigit(id, digit.as_bytes()[0] - b'0'))
+                }
+                keyboard::Key::Named(keyboard::key::Named::Escape) => Some(Message::CloseClock(id)),
+                keyboard::Key::Named(keyboard::key::Named::ArrowUp) => {
+                    Some(Message::Dock(id, dock::Anchor::Top))
+                }
+                keyboard::Key::Named(keyboard::key::Named::ArrowDown) => {
+                    Some(Message::Dock(id, dock::Anchor::Bottom))
+                }
+                keyboard::Key::Named(keyboard::key::Named::ArrowLeft) => {
+                    Some(Message::Dock(id, dock::Anchor::Left))
+                }
+                keyboard::Key::Named(keyboard::key::Named::ArrowRight) => {
+                    Some(Message::Dock(id, dock::Anchor::Right))
+                }
+                keyboard::Key::Character("r") => Some(Message::Reset(id)),
+                keyboard::Key::Character("m") => Some(Message::ToggleMode(id)),
+                keyboard::Key::Character("[") => Some(Message::DecreasePomodoroDuration(id)),
+                keyboard::Key::Character("]") => Some(Message::IncreasePomodoroDuration(id)),
+                keyboard::Key::Character("t") => Some(Message::ChangeTextColor(id)),
+                keyboard::Key::Character("b") => Some(Message::ChangeBackgroundColor(id)),
+                keyboard::Key::Character("c") => Some(Message::CycleTheme(id)),
+                keyboard::Key::Character("l") => Some(Message::Lap(id)),
+                keyboard::Key::Character("f") => Some(Message::FitToCalendar(id)),
+                keyboard::Key::Character("v") => Some(Message::ToggleLaps(id)),
+                keyboard::Key::Character("n") => Some(Message::NewClock),
+                keyboard::Key::Character("k") => Some(Message::KeepIdle(id)),
+                keyboard::Key::Character("d") => Some(Message::DiscardIdle(id)),
+                keyboard::Key::Character("s") => Some(Message::SkipBreak(id)),
+                keyboard::Key::Character("p") => Some(Message::PostponeBreak(id)),
+                _ => None,
+            }
+        }
+        fn handle_event(
+            event: iced::Event,
+            status: iced::event::Status,
+            id: iced::window::Id,
+        ) -> Option<Message> {
+            match event {
+                iced::Event::Keyboard(keyboard::Event::KeyPressed { key, .. })
+                    if status == iced::event::Status::Ignored =>
+                {
+                    handle_hotkey(key, id)
+                }
+                iced::Event::Window(iced::window::Event::Moved(position)) => {
+                    Some(Message::Moved(id, position))
+                }
+                iced::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                    Some(Message::ModifiersChanged(modifiers))
+                }
+                iced::Event::Mouse(iced::mouse::Event::WheelScrolled { delta }) => {
+                    let lines = match delta {
+                        iced::mouse::ScrollDelta::Lines { y, .. } => y,
+                        iced::mouse::ScrollDelta::Pixels { y, .. } => y / 20.0,
+                    };
+                    Some(Message::Scrolled(id, lines))
+                }
+                _ => None,
+            }
+        }
+        let animated = self
+            .reminders
+            .iter()
+            .map(|(_, reminder)| reminder)
+            .chain(
+                self.break_overlays
+                    .iter()
+                    .map(|(_, overlay)| &overlay.reminder),
+            )
+            .any(|reminder| {
+                matches!(
+                    self.media.status(reminder.picture.as_ref()),
+                    media::Status::Ready(picture) if picture.is_animated()
+                )
+            });
+        let animate = if animated {
+            time::every(Duration::from_millis(30)).map(|_| Message::Animate)
+        } else {
+            Subscription::none()
+        };
+        let check_calendar = if self.calendar.is_some() {
+            time::every(Duration::from_secs(15)).map(|_| Message::CheckCalendar)
+        } else {
+            Subscription::none()
+        };
+        let check_schedule = if self.schedule.is_some() {
+            time::every(Duration::from_secs(30)).map(|_| Message::CheckSchedule)
+        } else {
+            Subscription::none()
+        };
+        let api = match &self.api {
+            Some(api) => Subscription::run_with_id("api", api.commands()).map(Message::Api),
+            None => Subscription::none(),
+        };
+        let color_scheme = if self.tomato_config.light_theme.is_some()
+            || self.tomato_config.dark_theme.is_some()
+        {
+            Subscription::run(appearance::watch).map(Message::ColorSchemeChanged)
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch(vec![
+            tick,
+            settle_drag,
+            check_idle,
+            animate,
+            check_calendar,
+            check_schedule,
+            color_scheme,
+            api,
+            iced::event::listen_with(handle_event),
+        ])
+    }
+}
+
+impl TomatoClock {
+    fn new(palette: Palette, config: ClockConfig, position: Option<iced::Point>) -> Self {
+        let mode = config.mode.unwrap_or_default();
+        let pomodoro_duration = Duration::from_secs(config.pomodoro_minutes.unwrap_or(25) * 60);
+        let break_duration = config
+            .break_minutes
+            .map(|minutes| Duration::from_secs(minutes * 60));
+        let until = config.until.as_deref().and_then(|until| {
+            let time = parse_time_of_day(until);
+            if time.is_none() {
+                eprintln!("Invalid time of day {until:?}, expected HH:MM");
+            }
+            time
+        });
+        let mut clock = Self {
+            font: font(config.font_family.as_deref()),
+            duration: Duration::ZERO,
+            state: State::default(),
+            mode,
+            pomodoro_duration,
+            palette,
+            config,
+            position,
+            last_moved: None,
+            reminders_shown: 0,
+            session: None,
+            idle_prompt: None,
+            phase: Phase::default(),
+            break_duration,
+            postpones: 0,
+            scheme_theme: None,
+            show_laps: false,
+            until,
+            target: None,
+            entry: None,
+            notice: None,
+            hold_skipped: None,
+            progress: None,
+            dimmed: false,
+            hook_events: Vec::new(),
+            script_error: None,
+        };
+        clock.duration = clock.initial_duration();
+        clock
+    }
+
+    /// The duration the clock shows before it is started.
+    fn initial_duration(&self) -> Duration {
+        match self.mode {
+            Mode::Pomodoro => self.pomodoro_duration,
+            Mode::Stopwatch => Duration::ZERO,
+            Mode::Until => self
+                .until
+                .and_then(|until| next_occurrence(until, chrono::Local::now()))
+                .and_then(|target| (target - chrono::Local::now()).to_std().ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Switches to the theme of the desktop color scheme, or back to the clock's own theme if
+    /// the scheme has none.
+    fn follow_scheme(&mut self, config: &TomatoConfig, scheme_theme: Option<&str>) {
+        self.scheme_theme = scheme_theme.map(ToString::to_string);
+        self.palette = config.palette(&self.config, scheme_theme);
+    }
+
+    /// Ends an `until` countdown at its time of day and announces it.
+    fn arrive(&mut self, id: iced::window::Id) -> Task<Message> {
+        self.emit(hooks::Event::TimeOut);
+        self.finish_session(true);
+        self.target = None;
+        self.state = State::Idle;
+        self.duration = self.initial_duration();
+        Task::done(Message::TimeOut(id))
+    }
+
+    /// Moves on to the next phase once the current one has run out and announces it.
+    fn complete_phase(&mut self, id: iced::window::Id) -> Task<Message> {
+        self.emit(hooks::Event::TimeOut);
+        match (self.phase, self.break_duration) {
+            (Phase::Focus, Some(break_duration)) => {
+                self.finish_session(true);
+                self.phase = Phase::Break;
+                self.duration = break_duration;
+                self.state = State::Ticking {
+                    last_tick: Instant::now(),
+                };
+                self.emit(hooks::Event::BreakStart);
+            }
+            (Phase::Focus, None) => {
+                self.finish_session(true);
+                self.duration = self.pomodoro_duration;
+                self.state = State::Idle;
+            }
+            (Phase::Break, _) => self.skip_break(),
+        }
+        Task::done(Message::TimeOut(id))
+    }
+
+    fn skip_break(&mut self) {
+        self.phase = Phase::Focus;
+        self.postpones = 0;
+        self.duration = self.pomodoro_duration;
+        self.state = State::Idle;
+        self.emit(hooks::Event::BreakEnd);
+    }
+
+    /// Goes back to focusing for `delay`, after which the break starts again.
+    fn postpone_break(&mut self, delay: Duration) {
+        self.phase = Phase::Focus;
+        self.postpones += 1;
+        self.duration = delay;
+        self.session = Some(Session::new());
+        self.state = State::Ticking {
+            last_tick: Instant::now(),
+        };
+        self.emit(hooks::Event::BreakEnd);
+    }
+
+    /// What the hook commands and scripts learn about the clock.
+    fn api_status(&self, index: usize) -> api::ClockStatus {
+        let details = self.hook_details();
+        api::ClockStatus {
+            index,
+            label: self.config.label.clone(),
+            mode: details.mode,
+            phase: details.phase,
+            running: matches!(self.state, State::Ticking { .. }),
+            display: self.time_text(),
+            planned_secs: details.planned.map(|planned| planned.as_secs()),
+            elapsed_secs: details.elapsed.as_secs(),
+            remaining_secs: details.remaining.map(|remaining| remaining.as_secs()),
+            task: details.task,
+        }
+    }
+
+    fn hook_details(&self) -> hooks::Details {
+        hooks::Details {
+            mode: self.mode.name(),
+            phase: self.phase.name(),
+            planned: match (self.mode, self.phase) {
+                (Mode::Pomodoro, Phase::Focus) => Some(self.pomodoro_duration),
+                (Mode::Pomodoro, Phase::Break) => self.break_duration,
+                (Mode::Stopwatch, _) => None,
+                (Mode::Until, _) => self.session.as_ref().and_then(|session| {
+                    let target = self.target?.timestamp() as u64;
+                    Some(Duration::from_secs(
+                        target.saturating_sub(session.started_at),
+                    ))
+                }),
+            },
+            elapsed: self
+                .session
+                .as_ref()
+                .map(|session| session.focused)
+                .unwrap_or_default(),
+            remaining: match self.mode {
+                Mode::Pomodoro | Mode::Until => Some(self.duration),
+                Mode::Stopwatch => None,
+            },
+            task: self.config.label.clone(),
+        }
+    }
+
+    fn emit(&mut self, event: hooks::Event) {
+        let details = self.hook_details();
+        self.hook_events.push((event, details));
+    }
+
+    /// Reports a reset, unless there was nothing to reset.
+    fn emit_reset(&mut self) {
+        if self.session.is_some()
+            || self.phase == Phase::Break
+            || matches!(self.state, State::Ticking { .. })
+        {
+            self.emit(hooks::Event::Reset);
+        }
+    }
+
+    /// Writes the current session to the history, if anything was timed.
+    fn finish_session(&mut self, completed: bool) {
+        let Some(session) = self.session.take() else {
+            return;
+        };
+        self.idle_prompt = None;
+        if session.focused.is_zero() {
+            return;
+        }
+        history::append(&history::SessionRecord {
+            label: self.config.label.clone(),
+            mode: self.mode,
+            started_at: session.started_at,
+            ended_at: history::unix_now(),
+            planned_secs: match self.mode {
+                Mode::Pomodoro => Some(self.pomodoro_duration.as_secs()),
+                Mode::Stopwatch => None,
+                Mode::Until => self
+                    .target
+                    .map(|target| (target.timestamp() as u64).saturating_sub(session.started_at)),
+            },
+            focused_secs: session.focused.as_secs(),
+            discarded_idle_secs: session.discarded_idle.as_secs(),
+            laps_ms: session
+                .laps
+                .iter()
+                .map(|split| split.as_millis() as u64)
+                .collect(),
+            completed,
+        });
+    }
+
+    /// Holds a running pomodoro while `current` is on and resumes it afterwards, and warns if the
+    /// pomodoro would run into `next`.
+    fn follow_calendar(
+        &mut self,
+        current: Option<&calendar::Event>,
+        next: Option<&calendar::Event>,
+        hold: bool,
+        now: chrono::DateTime<chrono::Local>,
+    ) {
+        if !matches!(self.mode, Mode::Pomodoro) || self.phase != Phase::Focus {
+            self.notice = None;
+            return;
+        }
+        let held = matches!(self.notice, Some(calendar::Notice::Held(_)));
+        match current.filter(|event| hold && self.hold_skipped != Some(event.start)) {
+            Some(event) if held || matches!(self.state, State::Ticking { .. }) => {
+                if !held {
+                    self.state = State::Idle;
+                    self.emit(hooks::Event::Pause);
+                }
+                self.notice = Some(calendar::Notice::Held(event.clone()));
+                return;
+            }
+            _ if held => {
+                self.state = State::Ticking {
+                    last_tick: Instant::now(),
+                };
+                self.emit(hooks::Event::Resume);
+            }
+            _ => {}
+        }
+        let ends = now + chrono::TimeDelta::from_std(self.duration).unwrap_or_default();
+        self.notice = next
+            .filter(|event| event.start < ends)
+            .cloned()
+            .map(calendar::Notice::Ahead);
+    }
+
+    /// Stops a running pomodoro after `idle` without input and asks whether that time counts.
+    fn pause_for_idle(&mut self, idle: Duration) {
+        if !matches!(self.state, State::Ticking { .. })
+            || !matches!(self.mode, Mode::Pomodoro)
+            || self.phase != Phase::Focus
+        {
+            return;
+        }
+        let focused = self
+            .session
+            .as_ref()
+            .map(|session| session.focused)
+            .unwrap_or_default();
+        self.state = State::Idle;
+        self.idle_prompt = Some(idle.min(focused));
+        self.emit(hooks::Event::Pause);
+    }
+
+    fn time_format(&self) -> &time_format::TimeFormat {
+        static AUTO: time_format::TimeFormat = time_format::TimeFormat {
+            pomodoro: None,
+            stopwatch: None,
+            hide_seconds_while_running: false,
+        };
+        self.config.time_format.as_ref().unwrap_or(&AUTO)
+    }
+
+    /// The duration as shown in the clock window, or the time of day being typed.
+    fn time_text(&self) -> String {
+        if let Some(entry) = &self.entry {
+            let digits = format!("{entry:-<4}");
+            return format!("{}:{}", &digits[..2], &digits[2..]);
+        }
+        self.time_format().render(
+            self.duration,
+            self.mode,
+            matches!(self.state, State::Ticking { .. }),
+        )
+    }
+
+    /// Whether the clock changes more often than once a second, for tenths or a pulse.
+    fn animates(&self) -> bool {
+        self.time_format()
+            .shows_tenths(self.mode, matches!(self.state, State::Ticking { .. }))
+            || urgency::pulsing(self.urgency_rules(), self.duration)
+    }
+
+    /// The clock's current settings in the form written back to the config file.
+    fn saved_config(&self, monitors: &[monitors::Monitor]) -> ClockConfig {
+        let (position, position_monitor) = match self.position {
+            Some(point) => {
+                let (position, monitor) = monitors::locate(monitors, point);
+                (Some(position), monitor)
+            }
+            None => (self.config.position, self.config.position_monitor.clone()),
+        };
+        ClockConfig {
+            mode: Some(self.mode),
+            pomodoro_minutes: Some(self.pomodoro_duration.as_secs() / 60),
+            position,
+            position_monitor,
+            ..self.config.clone()
+        }
+    }
+
+    fn update(&mut self, id: iced::window::Id, message: Message) -> Task<Message> {
+        match message {
+            Message::Toggle(_) => match self.state {
                 State::Idle => {
+                    if let Some(calendar::Notice::Held(event)) = self.notice.take() {
+                        self.hold_skipped = Some(event.start);
+                    }
+                    if let Mode::Until = self.mode {
+                        if let Some(entry) = self.entry.take() {
+                            self.until = parse_time_of_day(&entry).or(self.until);
+                            self.config.until =
+                                self.until.map(|until| until.format("%H:%M").to_string());
+                        }
+                        let Some(target) = self
+                            .until
+                            .and_then(|until| next_occurrence(until, chrono::Local::now()))
+                        else {
+                            return Task::none();
+                        };
+                        self.target = Some(target);
+                    }
+                    self.idle_prompt = None;
+                    let resumed = self.session.is_some() || self.phase == Phase::Break;
+                    if self.phase == Phase::Focus {
+                        self.session.get_or_insert_with(Session::new);
+                    }
                     self.state = State::Ticking {
                         last_tick: Instant::now(),
                     };
+                    self.emit(if resumed {
+                        hooks::Event::Resume
+                    } else {
+                        hooks::Event::Start
+                    });
                 }
                 State::Ticking { .. } => {
                     self.state = State::Idle;
+                    self.emit(hooks::Event::Pause);
                 }
             },
-            Message::ToggleMode => {
-                self.state = State::Idle;
-                match self.mode {
-                    Mode::Pomodoro => {
-                        self.mode = Mode::Stopwatch;
-                        self.duration = Duration::ZERO;
-                    }
-                    Mode::Stopwatch => {
-                        self.mode = Mode::Pomodoro;
-                        self.duration = self.pomodoro_duration;
+            Message::TypeDigit(_, digit)
+                if matches!((self.mode, &self.state), (Mode::Until, State::Idle)) =>
+            {
+                let entry = self.entry.get_or_insert_with(String::new);
+                if entry.len() < 4 {
+                    entry.push(char::from(b'0' + digit));
+                }
+            }
+            Message::EraseDigit(_) => {
+                if let Some(entry) = &mut self.entry {
+                    entry.pop();
+                }
+            }
+            Message::FitToCalendar(_) => {
+                if let Some(calendar::Notice::Ahead(event)) = &self.notice {
+                    if let Some(left) = (event.start - chrono::Local::now())
+                        .to_std()
+                        .ok()
+                        .filter(|left| *left >= Duration::from_secs(60))
+                    {
+                        self.duration = left;
+                        self.notice = None;
                     }
+                }
+            }
+            Message::Lap(_) => {
+                if let (Mode::Stopwatch, State::Ticking { .. }, Some(session)) =
+                    (self.mode, &self.state, &mut self.session)
+                {
+                    session.laps.push(self.duration);
+                }
+            }
+            Message::ToggleMode(_) => {
+                self.emit_reset();
+                self.finish_session(false);
+                self.phase = Phase::Focus;
+                self.postpones = 0;
+                self.state = State::Idle;
+                self.mode = match self.mode {
+                    Mode::Pomodoro => Mode::Stopwatch,
+                    Mode::Stopwatch => Mode::Until,
+                    Mode::Until => Mode::Pomodoro,
                 };
+                self.target = None;
+                self.entry = None;
+                self.duration = self.initial_duration();
             }
             Message::Tick(now) => {
                 if let State::Ticking { last_tick } = &mut self.state {
-                    if let Mode::Pomodoro = &self.mode {
-                        if self.duration > Duration::ZERO + Duration::from_secs(1) {
-                            self.duration -= now - *last_tick;
+                    if let Some(session) = &mut self.session {
+                        session.focused += now - *last_tick;
+                    }
+                    match self.mode {
+                        Mode::Pomodoro => {
+                            if self.duration > Duration::ZERO + Duration::from_secs(1) {
+                                self.duration -= now - *last_tick;
+                                *last_tick = now;
+                            } else {
+                                return self.complete_phase(id);
+                            }
+                        }
+                        Mode::Stopwatch => {
+                            self.duration += now - *last_tick;
                             *last_tick = now;
-                        } else {
-                            self.duration = self.pomodoro_duration;
-                            self.state = State::Idle;
-                            return Task::done(Message::TimeOut);
                         }
-                    } else {
-                        self.duration += now - *last_tick;
-                        *last_tick = now;
+                        // Goes by the wall clock, so suspend and daylight saving changes count.
+                        Mode::Until => {
+                            *last_tick = now;
+                            match self
+                                .target
+                                .and_then(|target| (target - chrono::Local::now()).to_std().ok())
+                            {
+                                Some(remaining) if !remaining.is_zero() => {
+                                    self.duration = remaining
+                                }
+                                _ => return self.arrive(id),
+                            }
+                        }
                     }
-                };
+                } else if matches!(self.mode, Mode::Until) && self.entry.is_none() {
+                    self.duration = self.initial_duration();
+                }
             }
-            Message::EarlyTermination => {
+            Message::EarlyTermination(_) => {
                 if let Mode::Pomodoro = &self.mode {
-                    self.duration = self.pomodoro_duration;
-                    self.state = State::Idle;
-                    return Task::done(Message::TimeOut);
+                    return self.complete_phase(id);
                 }
             }
-            Message::Reset => {
-                match self.mode {
-                    Mode::Pomodoro => self.duration = self.pomodoro_duration,
-                    Mode::Stopwatch => self.duration = Duration::ZERO,
+            Message::KeepIdle(_) | Message::DiscardIdle(_) => {
+                let Some(idle) = self.idle_prompt.take() else {
+                    return Task::none();
+                };
+                if let (Message::DiscardIdle(_), Some(session)) = (message, &mut self.session) {
+                    session.focused = session.focused.saturating_sub(idle);
+                    session.discarded_idle += idle;
+                    self.duration = (self.duration + idle).min(self.pomodoro_duration);
                 }
+                self.state = State::Ticking {
+                    last_tick: Instant::now(),
+                };
+                self.emit(hooks::Event::Resume);
+            }
+            Message::Reset(_) => {
+                self.emit_reset();
+                self.finish_session(false);
+                self.phase = Phase::Focus;
+                self.postpones = 0;
+                self.target = None;
+                self.entry = None;
+                self.notice = None;
+                self.duration = self.initial_duration();
                 self.state = State::Idle;
             }
-            Message::IncreasePomodoroDuration => {
+            Message::IncreasePomodoroDuration(_)
                 if matches!(self.state, State::Idle)
                     && matches!(self.mode, Mode::Pomodoro)
-                    && self.pomodoro_duration < Duration::from_secs(60 * 60)
-                {
-                    self.pomodoro_duration += Duration::from_secs(5 * 60);
-                    self.duration = self.pomodoro_duration;
-                };
+                    && self.phase == Phase::Focus
+                    && self.pomodoro_duration < Duration::from_secs(60 * 60) =>
+            {
+                self.pomodoro_duration += Duration::from_secs(5 * 60);
+                self.duration = self.pomodoro_duration;
             }
-            Message::DecreasePomodoroDuration => {
+            Message::DecreasePomodoroDuration(_)
                 if matches!(self.state, State::Idle)
                     && matches!(self.mode, Mode::Pomodoro)
-                    && self.pomodoro_duration > Duration::from_secs(5 * 60)
-                {
-                    self.pomodoro_duration -= Duration::from_secs(5 * 60);
-                    self.duration = self.pomodoro_duration;
-                }
+                    && self.phase == Phase::Focus
+                    && self.pomodoro_duration > Duration::from_secs(5 * 60) =>
+            {
+                self.pomodoro_duration -= Duration::from_secs(5 * 60);
+                self.duration = self.pomodoro_duration;
             }
             _ => {}
         }
         Task::none()
     }
-    fn subscription(&self) -> Subscription<Message> {
-        let tick = match self.state {
-            State::Idle => Subscription::none(),
-            State::Ticking { .. } => time::every(Duration::from_millis(1000)).map(Message::Tick), // equal to |instant| Message::Tick(instant),
+    fn view(&self, id: iced::window::Id) -> Element<'_, Message> {
+        let scale = self.config.scale.unwrap_or(1.0);
+        let mut bounds = clock_size(&self.config);
+        let header = match (&self.config.label, &self.progress) {
+            (Some(label), Some(progress)) => Some(format!("{label}  {progress}")),
+            (label, progress) => label.clone().or_else(|| progress.clone()),
         };
-        fn handle_hotkey(key: keyboard::Key, _modifiers: keyboard::Modifiers) -> Option<Message> {
-            match key.as_ref() {
-                keyboard::Key::Named(keyboard::key::Named::Space) => Some(Message::Toggle),
-                keyboard::Key::Named(keyboard::key::Named::Escape) => Some(Message::Shutdown),
-                keyboard::Key::Character("r") => Some(Message::Reset),
-                keyboard::Key::Character("m") => Some(Message::ToggleMode),
-                keyboard::Key::Character("[") => Some(Message::DecreasePomodoroDuration),
-                keyboard::Key::Character("]") => Some(Message::IncreasePomodoroDuration),
-                keyboard::Key::Character("t") => Some(Message::ChangeTextColor),
-                keyboard::Key::Character("b") => Some(Message::ChangeBackgroundColor),
-                _ => None,
-            }
+        let header = self
+            .script_error
+            .as_ref()
+            .map(|_| "script error".to_string())
+            .or(header);
+        if header.is_some() || self.notice.is_some() {
+            bounds.height -= LABEL_HEIGHT * scale;
         }
-        Subscription::batch(vec![tick, keyboard::on_key_press(handle_hotkey)])
-    }
-    fn view(&self) -> Element<Message> {
-        const MINUTE: u64 = 60;
-        const HOUR: u64 = MINUTE * 60;
+        let time = self.time_text();
+        let size = fit_text_size(&time, bounds);
+        let duration = text(time)
+            .font(self.font)
+            .color(self.text_color())
+            .size(size)
+            .line_height(iced::widget::text::LineHeight::Absolute(iced::Pixels(size)));
 
-        let seconds = self.duration.as_secs();
-        let duration = text!(
-            "{:0>2}:{:0>2}:{:0>2}",
-            seconds / HOUR,
-            (seconds % HOUR) / MINUTE,
-            seconds % MINUTE,
-        )
-        .color(if matches!(self.state, State::Idle) {
-            self.stop_text_color
+        let content: Element<Message> = if let Some(idle) = self.idle_prompt {
+            column![
+                text!("away {} min", idle.as_secs() / 60)
+                    .font(self.font)
+                    .size(12.0 * scale)
+                    .color(self.palette.stop_text),
+                iced::widget::row![
+                    MouseArea::new(
+                        text("keep")
+                            .font(self.font)
+                            .size(16.0 * scale)
+                            .color(self.palette.stop_text)
+                    )
+                    .on_press(Message::KeepIdle(id)),
+                    MouseArea::new(
+                        text("discard")
+                            .font(self.font)
+                            .size(16.0 * scale)
+                            .color(self.palette.stop_text)
+                    )
+                    .on_press(Message::DiscardIdle(id)),
+                ]
+                .spacing(12.0 * scale)
+            ]
+            .align_x(iced::Alignment::Center)
+            .into()
+        } else if let Some(notice) = &self.notice {
+            column![
+                MouseArea::new(
+                    text(notice.text())
+                        .font(self.font)
+                        .size(12.0 * scale)
+                        .color(self.text_color())
+                )
+                .on_press(Message::FitToCalendar(id)),
+                duration
+            ]
+            .align_x(iced::Alignment::Center)
+            .into()
+        } else if let Some(label) = header {
+            column![
+                text(label)
+                    .font(self.font)
+                    .size(12.0 * scale)
+                    .color(self.text_color()),
+                duration
+            ]
+            .align_x(iced::Alignment::Center)
+            .into()
         } else {
-            self.run_text_color
-        })
-        .size(40)
-        .line_height(iced::widget::text::LineHeight::Absolute(iced::Pixels(
-            40f32,
-        )));
-
-        MouseArea::new(center(duration))
-            .on_press(Message::StartDragging)
-            .on_right_press(Message::EarlyTermination)
+            duration.into()
+        };
+
+        let clock = center(content).height(clock_size(&self.config).height);
+        let content: Element<Message> = if self.show_laps {
+            column![clock, self.laps_view()].into()
+        } else {
+            clock.into()
+        };
+        MouseArea::new(content)
+            .on_press(Message::StartDragging(id))
+            .on_right_press(Message::EarlyTermination(id))
             .into()
     }
-    fn theme(&self) -> Theme {
-        if let State::Idle = self.state {
-            Theme::custom(
-                "stop".to_string(),
-                iced::theme::Palette {
-                    background: self.stop_background_color.into(),
-                    ..Theme::default().palette()
-                },
-            )
-        } else {
-            Theme::custom(
-                "run".to_string(),
-                iced::theme::Palette {
-                    background: self.run_background_color.into(),
-                    ..Theme::default().palette()
-                },
-            )
+
+    /// The latest laps with their lap and split times.
+    fn laps_view(&self) -> Element<'_, Message> {
+        let scale = self.config.scale.unwrap_or(1.0);
+        let splits = self
+            .session
+            .as_ref()
+            .map_or(&[][..], |session| session.laps.as_slice());
+        let format = |duration| self.time_format().render(duration, Mode::Stopwatch, false);
+        let lines = recent_laps(splits).map(|(number, lap, split)| {
+            text!("{number}  {}  {}", format(lap), format(split))
+                .font(self.font)
+                .size(11.0 * scale)
+                .line_height(iced::widget::text::LineHeight::Absolute(iced::Pixels(
+                    LAP_HEIGHT * scale,
+                )))
+                .color(self.palette.stop_text)
+                .into()
+        });
+        column(lines)
+            .width(iced::Length::Fill)
+            .align_x(iced::Alignment::Center)
+            .into()
+    }
+
+    /// The window size, including the lap list if it is shown.
+    fn window_size(&self) -> iced::Size {
+        let mut size = clock_size(&self.config);
+        if self.show_laps {
+            size.height += VISIBLE_LAPS as f32 * LAP_HEIGHT * self.config.scale.unwrap_or(1.0);
         }
+        size
+    }
+
+    /// The urgency rules in effect, which only apply to a running pomodoro or break.
+    fn urgency_rules(&self) -> &[urgency::UrgencyRule] {
+        match (&self.state, self.mode) {
+            (State::Ticking { .. }, Mode::Pomodoro | Mode::Until) => {
+                self.config.urgency.as_deref().unwrap_or_default()
+            }
+            _ => &[],
+        }
+    }
+
+    /// The text and background colors, recolored by the urgency rules.
+    fn colors(&self) -> (iced::Color, iced::Color) {
+        let (text, background) = match (&self.state, self.phase) {
+            (State::Idle, _) => (self.palette.stop_text, self.palette.stop_background),
+            (_, Phase::Break) => (self.palette.break_text, self.palette.break_background),
+            _ => (self.palette.run_text, self.palette.run_background),
+        };
+        let (mut text, mut background) =
+            urgency::apply(self.urgency_rules(), self.duration, text, background);
+        if self.dimmed {
+            text.a *= DIMMED_ALPHA;
+            background.a *= DIMMED_ALPHA;
+        }
+        (text, background)
+    }
+
+    fn text_color(&self) -> iced::Color {
+        self.colors().0
+    }
+
+    fn theme(&self, base: iced::theme::Palette) -> Theme {
+        let name = match (&self.state, self.phase) {
+            (State::Idle, _) => "stop",
+            (_, Phase::Break) => "break",
+            _ => "run",
+        };
+        let background = self.colors().1;
+        Theme::custom(
+            name.to_string(),
+            iced::theme::Palette { background, ..base },
+        )
     }
 }
 
@@ -566,43 +3034,44 @@ impl Reminder {
             reminder.text = text.clone();
         }
         if let Some(color) = color {
-            reminder.color = color.clone().into();
+            reminder.color = (*color).into();
         }
         if let Some(font_size) = font_size {
-            reminder.font_size = font_size.clone();
+            reminder.font_size = *font_size;
         }
         reminder.width = *width;
         reminder.height = *height;
         reminder
     }
 
-    fn view<'a>(&'a self, picture: Option<&'a Picture>) -> Element<Message> {
-        match picture {
-            Some(Picture::ImageHandle(handle)) => {
-                let mut picture = iced::widget::image(handle);
+    fn view<'a>(
+        &'a self,
+        id: iced::window::Id,
+        picture: media::Status<'a>,
+    ) -> Element<'a, Message> {
+        MouseArea::new(center(self.content(picture)))
+            .on_press(Message::CloseReminder(id))
+            .into()
+    }
+
+    /// The picture, with the text as its caption if enabled, or the text if there is none.
+    fn content<'a>(&'a self, picture: media::Status<'a>) -> Element<'a, Message> {
+        let picture: Element<'a, Message> = match picture {
+            media::Status::Ready(picture) => {
+                let mut picture =
+                    iced::widget::image(picture.frame(self.shown_at.elapsed()).clone());
                 if let Some(width) = self.width {
                     picture = picture.width(width)
                 }
                 if let Some(height) = self.height {
                     picture = picture.height(height)
                 }
-                MouseArea::new(center(picture))
-                    .on_press(Message::CloseReminder)
-                    .into()
+                picture.into()
             }
-            Some(Picture::GifFrams(frames)) => {
-                let mut picture: gif::Gif<'a> = gif(frames);
-                if let Some(width) = self.width {
-                    picture = picture.width(iced::Length::from(width))
-                }
-                if let Some(height) = self.height {
-                    picture = picture.height(iced::Length::from(height))
-                }
-                MouseArea::new(center(picture))
-                    .on_press(Message::CloseReminder)
-                    .into()
+            media::Status::Loading => {
+                return iced::widget::Space::new(iced::Length::Shrink, iced::Length::Shrink).into();
             }
-            None => {
+            media::Status::Unavailable => {
                 let mut _text = text(&self.text)
                     .color(self.color)
                     .size(self.font_size)
@@ -613,10 +3082,482 @@ impl Reminder {
                 if let Some(height) = self.height {
                     _text = _text.height(height)
                 }
-                MouseArea::new(center(_text))
-                    .on_press(Message::CloseReminder)
-                    .into()
+                return _text.into();
             }
+        };
+        if self.caption {
+            column![
+                picture,
+                text(&self.text)
+                    .color(self.color)
+                    .size(self.font_size / 3)
+                    .center()
+            ]
+            .align_x(iced::Alignment::Center)
+            .into()
+        } else {
+            picture
+        }
+    }
+}
+
+impl BreakOverlay {
+    fn view<'a>(
+        &'a self,
+        id: iced::window::Id,
+        clock: &'a TomatoClock,
+        picture: media::Status<'a>,
+        skips_left: u32,
+        can_postpone: bool,
+    ) -> Element<'a, Message> {
+        let action_size = (self.reminder.font_size / 6).max(16);
+        let mut actions = iced::widget::row![].spacing(action_size * 2);
+        if skips_left > 0 {
+            actions = actions.push(
+                MouseArea::new(
+                    text!("skip ({skips_left} left)")
+                        .color(self.reminder.color)
+                        .size(action_size),
+                )
+                .on_press(Message::SkipBreak(id)),
+            );
+        }
+        if can_postpone {
+            actions = actions.push(
+                MouseArea::new(
+                    text("postpone")
+                        .color(self.reminder.color)
+                        .size(action_size),
+                )
+                .on_press(Message::PostponeBreak(id)),
+            );
+        }
+        center(
+            column![
+                self.reminder.content(picture),
+                text(clock.time_text())
+                    .color(self.reminder.color)
+                    .size(self.reminder.font_size / 2),
+                actions,
+            ]
+            .spacing(action_size)
+            .align_x(iced::Alignment::Center),
+        )
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A daemon with the main clock and the clocks of `config` open, and their window ids.
+    fn daemon(config: TomatoConfig) -> (AppDaemon, Vec<iced::window::Id>) {
+        let mut daemon = AppDaemon::with_config(config, false);
+        let mut configs = vec![daemon.tomato_config.main_clock()];
+        configs.extend(daemon.tomato_config.clocks.iter().cloned());
+        for config in &configs {
+            let _ = daemon.spawn_clock(config);
+        }
+        let ids = daemon.clocks.iter().map(|(id, _)| *id).collect();
+        (daemon, ids)
+    }
+
+    #[test]
+    fn runs_every_clock_on_its_own() {
+        let (mut daemon, ids) = daemon(TomatoConfig {
+            run_text_color_index: 1,
+            text_colors: vec![
+                Color {
+                    r: 0.0,
+                    g: 0.0,
+                    b: 0.0,
+                    a: 1.0,
+                };
+                2
+            ],
+            clocks: vec![ClockConfig {
+                label: Some("Reading".to_string()),
+                pomodoro_minutes: Some(50),
+                ..ClockConfig::default()
+            }],
+            ..TomatoConfig::default()
+        });
+        let [main, reading] = ids[..] else {
+            panic!("{ids:?}");
+        };
+        let (_, clock) = &daemon.clocks[1];
+        assert_eq!(clock.config.label.as_deref(), Some("Reading"));
+        assert_eq!(clock.pomodoro_duration, Duration::from_secs(50 * 60));
+        // Unset fields fall back to the main clock.
+        assert_eq!(clock.config.run_text_color_index, Some(1));
+
+        let _ = daemon.update(Message::Toggle(reading));
+        assert!(matches!(daemon.clocks[0].1.state, State::Idle));
+        assert!(matches!(daemon.clocks[1].1.state, State::Ticking { .. }));
+
+        let _ = daemon.update(Message::NewClock);
+        let (added, clock) = &daemon.clocks[2];
+        let added = *added;
+        assert_eq!(clock.config.label, None);
+        assert_eq!(clock.pomodoro_duration, Duration::from_secs(25 * 60));
+
+        let _ = daemon.update(Message::CloseClock(reading));
+        let open = daemon.clocks.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        assert_eq!(open, [main, added]);
+        // Closing the main clock quits instead.
+        let _ = daemon.update(Message::CloseClock(main));
+        assert_eq!(daemon.clocks.len(), 2);
+    }
+
+    /// A clock opened with `config`, as the daemon opens it.
+    fn clock(config: ClockConfig) -> TomatoClock {
+        let (mut daemon, _) = daemon(TomatoConfig {
+            clocks: vec![config],
+            ..TomatoConfig::default()
+        });
+        daemon.clocks.remove(1).1
+    }
+
+    #[test]
+    fn pauses_focusing_pomodoros_for_idle_time() {
+        let id = iced::window::Id::unique();
+        let mut pomodoro = clock(ClockConfig::default());
+        let _ = pomodoro.update(id, Message::Toggle(id));
+        assert!(matches!(pomodoro.state, State::Ticking { .. }));
+        pomodoro.session.as_mut().unwrap().focused = Duration::from_secs(120);
+        pomodoro.pause_for_idle(Duration::from_secs(600));
+        assert!(matches!(pomodoro.state, State::Idle));
+        // Only the time focused can be discarded.
+        assert_eq!(pomodoro.idle_prompt, Some(Duration::from_secs(120)));
+
+        let mut stopwatch = clock(ClockConfig {
+            mode: Some(Mode::Stopwatch),
+            ..ClockConfig::default()
+        });
+        let _ = stopwatch.update(id, Message::Toggle(id));
+        stopwatch.pause_for_idle(Duration::from_secs(600));
+        assert!(matches!(stopwatch.state, State::Ticking { .. }));
+        assert_eq!(stopwatch.idle_prompt, None);
+    }
+
+    #[test]
+    fn takes_a_break_after_every_pomodoro() {
+        let id = iced::window::Id::unique();
+        let mut pomodoro = clock(ClockConfig {
+            break_minutes: Some(5),
+            ..ClockConfig::default()
+        });
+        let _ = pomodoro.update(id, Message::Toggle(id));
+        let _ = pomodoro.update(id, Message::EarlyTermination(id));
+        assert_eq!(pomodoro.phase, Phase::Break);
+        assert_eq!(pomodoro.duration, Duration::from_secs(5 * 60));
+        assert!(matches!(pomodoro.state, State::Ticking { .. }));
+        assert!(pomodoro.session.is_none());
+
+        let _ = pomodoro.update(id, Message::EarlyTermination(id));
+        assert_eq!(pomodoro.phase, Phase::Focus);
+        assert_eq!(pomodoro.duration, Duration::from_secs(25 * 60));
+        assert!(matches!(pomodoro.state, State::Idle));
+
+        let mut without_breaks = clock(ClockConfig::default());
+        let _ = without_breaks.update(id, Message::Toggle(id));
+        let _ = without_breaks.update(id, Message::EarlyTermination(id));
+        assert_eq!(without_breaks.phase, Phase::Focus);
+        assert!(matches!(without_breaks.state, State::Idle));
+    }
+
+    #[test]
+    fn keeps_strict_breaks_until_postponed() {
+        let (mut daemon, ids) = daemon(TomatoConfig {
+            break_minutes: Some(5),
+            strict_break: Some(StrictBreakConfig {
+                skips_per_day: 0,
+                postpone_minutes: Some(10),
+                max_postpones: None,
+                background: None,
+            }),
+            ..TomatoConfig::default()
+        });
+        let id = ids[0];
+        let phase = |daemon: &AppDaemon| daemon.clocks[0].1.phase;
+        let _ = daemon.update(Message::Toggle(id));
+        let _ = daemon.update(Message::EarlyTermination(id));
+        assert_eq!(phase(&daemon), Phase::Break);
+
+        for message in [
+            Message::Reset(id),
+            Message::ToggleMode(id),
+            Message::EarlyTermination(id),
+            Message::SkipBreak(id),
+        ] {
+            let _ = daemon.update(message);
+            assert_eq!(phase(&daemon), Phase::Break, "{message:?}");
+        }
+
+        let _ = daemon.update(Message::PostponeBreak(id));
+        let (_, clock) = &daemon.clocks[0];
+        assert_eq!(clock.phase, Phase::Focus);
+        assert_eq!(clock.duration, Duration::from_secs(10 * 60));
+        assert!(matches!(clock.state, State::Ticking { .. }));
+
+        // The postponed break comes back and can be postponed only once.
+        let _ = daemon.update(Message::EarlyTermination(id));
+        let _ = daemon.update(Message::PostponeBreak(id));
+        assert_eq!(phase(&daemon), Phase::Break);
+    }
+
+    #[test]
+    fn leaks_every_font_name_once() {
+        let font = |name| match font(Some(name)).family {
+            iced::font::Family::Name(name) => name,
+            family => panic!("{family:?}"),
+        };
+        assert!(std::ptr::eq(font("Fira Mono"), font("Fira Mono")));
+        assert_eq!(font("Fira Sans"), "Fira Sans");
+        assert_eq!(super::font(Some("monospace")), iced::Font::MONOSPACE);
+    }
+
+    #[derive(Deserialize)]
+    struct Colored {
+        color: Color,
+    }
+
+    fn parse_color(value: &str) -> Result<iced::Color, toml::de::Error> {
+        toml::from_str::<Colored>(&format!("color = {value}")).map(|colored| colored.color.into())
+    }
+
+    #[test]
+    fn parses_hex_and_rgba_colors() {
+        assert_eq!(
+            parse_color(r##""#ff8000""##).unwrap(),
+            iced::Color::from_rgba(1.0, 128.0 / 255.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            parse_color(r#""00000080""#).unwrap(),
+            iced::Color::from_rgba(0.0, 0.0, 0.0, 128.0 / 255.0)
+        );
+        assert_eq!(
+            parse_color("{r = 0.5, g = 0.25, b = 1, a = 0.8}").unwrap(),
+            iced::Color::from_rgba(0.5, 0.25, 1.0, 0.8)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_hex_colors() {
+        for value in [
+            r##""#fff""##,
+            r##""#ff80001""##,
+            r##""#gg0000""##,
+            r##""#+f+f+f""##,
+        ] {
+            assert!(parse_color(value).is_err(), "{value}");
         }
     }
+
+    #[test]
+    fn follows_the_color_scheme_without_saving_its_theme() {
+        let theme = |hex: &str| ThemeConfig {
+            run_text: Some(Color::try_from(ColorValue::Hex(hex.to_string())).unwrap()),
+            ..ThemeConfig::default()
+        };
+        let config = TomatoConfig {
+            theme: Some("own".to_string()),
+            dark_theme: Some("night".to_string()),
+            themes: BTreeMap::from([
+                ("own".to_string(), theme("#ff0000")),
+                ("night".to_string(), theme("#0000ff")),
+            ]),
+            ..TomatoConfig::default()
+        };
+        let red = iced::Color::from_rgb(1.0, 0.0, 0.0);
+        let blue = iced::Color::from_rgb(0.0, 0.0, 1.0);
+        let resolved = config.resolve(&config.main_clock());
+        let mut clock = TomatoClock::new(config.palette(&resolved, None), resolved, None);
+        assert_eq!(clock.palette.run_text, red);
+
+        clock.follow_scheme(
+            &config,
+            config.scheme_theme(Some(appearance::ColorScheme::Dark)),
+        );
+        assert_eq!(clock.palette.run_text, blue);
+        assert_eq!(clock.saved_config(&[]).theme.as_deref(), Some("own"));
+
+        // Without a light theme the clock goes back to its own.
+        clock.follow_scheme(
+            &config,
+            config.scheme_theme(Some(appearance::ColorScheme::Light)),
+        );
+        assert_eq!(clock.palette.run_text, red);
+        assert_eq!(clock.saved_config(&[]).theme.as_deref(), Some("own"));
+    }
+
+    #[test]
+    fn fills_in_the_reminder_placeholders() {
+        assert_eq!(
+            render_template(
+                "{task}: {next_phase}, then {next_phase}",
+                Phase::Break,
+                "Essay"
+            ),
+            "Essay: break, then break"
+        );
+        assert_eq!(
+            render_template("Back to {next_phase} {other}", Phase::Focus, ""),
+            "Back to focus {other}"
+        );
+        let completed = render_template("{completed_today} done", Phase::Focus, "");
+        let count = completed.strip_suffix(" done").unwrap();
+        assert!(count.parse::<u32>().is_ok(), "{completed}");
+    }
+
+    #[test]
+    fn picks_reminders_in_turn_or_at_random() {
+        let config =
+            |order| toml::from_str::<ReminderConfig>(&format!(r#"order = "{order}""#)).unwrap();
+        let rotation = config("rotation");
+        let picks = (0..5).map(|shown| rotation.pick(3, shown).unwrap());
+        assert_eq!(picks.collect::<Vec<_>>(), [0, 1, 2, 0, 1]);
+        assert_eq!(rotation.pick(0, 4), None);
+
+        let random = config("random");
+        assert!((0..100).all(|shown| random.pick(3, shown).is_some_and(|index| index < 3)));
+        assert_eq!(random.pick(1, 7), Some(0));
+        assert_eq!(random.pick(0, 0), None);
+    }
+
+    #[test]
+    fn rotates_the_texts_and_images_of_each_clock() {
+        let reminder = toml::from_str(
+            r#"
+            text = "{task}: time to {next_phase}"
+            texts = ["Well done"]
+            image_path = "a.png"
+            image_paths = ["b.png", "c.png"]
+            width = 300
+            "#,
+        )
+        .unwrap();
+        let (mut daemon, ids) = daemon(TomatoConfig {
+            reminder,
+            clocks: vec![ClockConfig {
+                label: Some("Essay".to_string()),
+                ..ClockConfig::default()
+            }],
+            ..TomatoConfig::default()
+        });
+        let mut next = |id| {
+            let reminder = daemon.next_reminder(id).unwrap();
+            let picture = reminder.picture.unwrap();
+            assert_eq!(picture.width, Some(300));
+            (reminder.text, picture.path)
+        };
+        let shown = (0..4).map(|_| next(ids[1])).collect::<Vec<_>>();
+        let expected = [
+            ("Essay: time to focus", "a.png"),
+            ("Well done", "b.png"),
+            ("Essay: time to focus", "c.png"),
+            ("Well done", "a.png"),
+        ];
+        assert_eq!(
+            shown,
+            expected.map(|(text, path)| (text.to_string(), path.to_string()))
+        );
+        // Every clock counts its own reminders.
+        assert_eq!(
+            next(ids[0]),
+            (": time to focus".to_string(), "a.png".to_string())
+        );
+    }
+
+    #[test]
+    fn records_laps_of_a_running_stopwatch() {
+        let id = iced::window::Id::unique();
+        let laps = |clock: &TomatoClock| clock.session.as_ref().unwrap().laps.clone();
+        let mut stopwatch = clock(ClockConfig {
+            mode: Some(Mode::Stopwatch),
+            ..ClockConfig::default()
+        });
+        let _ = stopwatch.update(id, Message::Toggle(id));
+        stopwatch.duration = Duration::from_secs(5);
+        let _ = stopwatch.update(id, Message::Lap(id));
+        stopwatch.duration = Duration::from_secs(12);
+        let _ = stopwatch.update(id, Message::Lap(id));
+        let _ = stopwatch.update(id, Message::Toggle(id));
+        let _ = stopwatch.update(id, Message::Lap(id));
+        assert_eq!(laps(&stopwatch), [5, 12].map(Duration::from_secs));
+
+        let mut pomodoro = clock(ClockConfig::default());
+        let _ = pomodoro.update(id, Message::Toggle(id));
+        let _ = pomodoro.update(id, Message::Lap(id));
+        assert!(laps(&pomodoro).is_empty());
+    }
+
+    #[test]
+    fn lists_the_latest_laps_with_their_lap_and_split_times() {
+        let splits = [1, 3, 6].map(Duration::from_secs);
+        let laps = recent_laps(&splits).collect::<Vec<_>>();
+        let expected = [(1, 1, 1), (2, 2, 3), (3, 3, 6)].map(|(number, lap, split)| {
+            (number, Duration::from_secs(lap), Duration::from_secs(split))
+        });
+        assert_eq!(laps, expected);
+
+        let splits = (1..=VISIBLE_LAPS as u64 + 2).map(|minutes| Duration::from_secs(minutes * 60));
+        let splits = splits.collect::<Vec<_>>();
+        let laps = recent_laps(&splits).collect::<Vec<_>>();
+        assert_eq!(laps.len(), VISIBLE_LAPS);
+        assert_eq!(
+            laps[0],
+            (3, Duration::from_secs(60), Duration::from_secs(180))
+        );
+        assert_eq!(laps[VISIBLE_LAPS - 1].0, VISIBLE_LAPS + 2);
+    }
+
+    #[test]
+    fn grows_the_window_for_the_lap_list() {
+        let (mut daemon, ids) = daemon(TomatoConfig {
+            scale: Some(2.0),
+            ..TomatoConfig::default()
+        });
+        let height = |daemon: &AppDaemon| daemon.clocks[0].1.window_size().height;
+        let closed = height(&daemon);
+        let _ = daemon.update(Message::ToggleLaps(ids[0]));
+        assert_eq!(
+            height(&daemon),
+            closed + VISIBLE_LAPS as f32 * LAP_HEIGHT * 2.0
+        );
+        let _ = daemon.update(Message::ToggleLaps(ids[0]));
+        assert_eq!(height(&daemon), closed);
+    }
+
+    #[test]
+    fn idle_until_clocks_keep_counting_down() {
+        let until = chrono::Local::now() + chrono::TimeDelta::hours(2);
+        let config = ClockConfig {
+            mode: Some(Mode::Until),
+            until: Some(until.format("%H:%M").to_string()),
+            ..ClockConfig::default()
+        };
+        let config = TomatoConfig::default().resolve(&config);
+        let palette = TomatoConfig::default().palette(&config, None);
+        let mut clock = TomatoClock::new(palette, config, None);
+        let shown = clock.duration;
+        assert!(shown > Duration::from_secs(60 * 60) && shown <= Duration::from_secs(2 * 60 * 60));
+
+        clock.duration = Duration::ZERO;
+        let _ = clock.update(iced::window::Id::unique(), Message::Tick(Instant::now()));
+        assert!(matches!(clock.state, State::Idle));
+        assert!(clock.duration > Duration::from_secs(60 * 60));
+    }
+
+    #[test]
+    fn names_the_messages_scripts_see() {
+        let id = iced::window::Id::unique();
+        assert_eq!(Message::SkipBreak(id).name(), "SkipBreak");
+        assert_eq!(Message::Dock(id, dock::Anchor::Top).name(), "Dock");
+        assert!(Message::Toggle(id).reaches_scripts());
+        assert!(Message::NewClock.reaches_scripts());
+        assert!(!Message::Animate.reaches_scripts());
+        assert!(!Message::Tick(Instant::now()).reaches_scripts());
+    }
 }
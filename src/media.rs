@@ -0,0 +1,480 @@
+//! Reminder images, decoded on a background thread the first time a reminder shows them and
+//! kept for later reminders. GIFs, animated PNGs and animated WebPs play as animations.
+
+use iced::{futures::channel::oneshot, widget::image::Handle};
+use image::AnimationDecoder;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// An image file shown at most `width`×`height`; larger images are scaled down when decoded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Source {
+    pub path: String,
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+}
+
+/// A decoded image; still images have a single frame.
+pub struct Picture {
+    frames: Vec<(Handle, Duration)>,
+    length: Duration,
+}
+
+impl Picture {
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    /// The frame to show `elapsed` after the picture appeared, looping the animation.
+    pub fn frame(&self, elapsed: Duration) -> &Handle {
+        if self.length.is_zero() {
+            return &self.frames[0].0;
+        }
+        let mut offset = Duration::from_nanos((elapsed.as_nanos() % self.length.as_nanos()) as u64);
+        for (handle, delay) in &self.frames {
+            if offset < *delay {
+                return handle;
+            }
+            offset -= *delay;
+        }
+        &self.frames[0].0
+    }
+}
+
+pub enum Status<'a> {
+    Loading,
+    Ready(&'a Picture),
+    /// There is no picture or it failed to load, so the reminder shows its text.
+    Unavailable,
+}
+
+enum Entry {
+    Loading,
+    Loaded(Picture),
+    /// Failed to load the file as it was last modified then, so it is tried again once it changes.
+    Failed(Option<SystemTime>),
+}
+
+type Inbox = Arc<Mutex<Vec<(Source, Option<SystemTime>, Result<Picture, String>)>>>;
+
+/// When the file at `path` was last modified, if it exists.
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[derive(Default)]
+pub struct Cache {
+    entries: HashMap<Source, Entry>,
+    /// Pictures decoded in the background, waiting for [`Cache::collect`].
+    inbox: Inbox,
+}
+
+impl Cache {
+    /// Starts decoding `source` unless it is cached, already loading or failed to load and has
+    /// not changed since. The returned future completes once the result can be picked up with
+    /// [`Cache::collect`].
+    pub fn request(&mut self, source: &Source) -> Option<impl Future<Output = ()>> {
+        match self.entries.get(source) {
+            Some(Entry::Failed(failed)) if *failed != modified(&source.path) => {}
+            Some(_) => return None,
+            None => {}
+        }
+        self.entries.insert(source.clone(), Entry::Loading);
+        let (sender, receiver) = oneshot::channel();
+        let source = source.clone();
+        let inbox = self.inbox.clone();
+        std::thread::spawn(move || {
+            let modified = modified(&source.path);
+            let result = decode(&source);
+            if let Ok(mut inbox) = inbox.lock() {
+                inbox.push((source, modified, result));
+            }
+            let _ = sender.send(());
+        });
+        Some(async move {
+            let _ = receiver.await;
+        })
+    }
+
+    /// Moves the decoded pictures into the cache, logging the ones that failed.
+    pub fn collect(&mut self) {
+        let Ok(mut inbox) = self.inbox.lock() else {
+            return;
+        };
+        for (source, modified, result) in inbox.drain(..) {
+            let entry = match result {
+                Ok(picture) => Entry::Loaded(picture),
+                Err(e) => {
+                    eprintln!(
+                        "Failed to load reminder image {}: {e}; showing the text instead",
+                        source.path
+                    );
+                    Entry::Failed(modified)
+                }
+            };
+            self.entries.insert(source, entry);
+        }
+    }
+
+    pub fn status(&self, source: Option<&Source>) -> Status<'_> {
+        match source.and_then(|source| self.entries.get(source)) {
+            Some(Entry::Loading) => Status::Loading,
+            Some(Entry::Loaded(picture)) => Status::Ready(picture),
+            Some(Entry::Failed(_)) | None => Status::Unavailable,
+        }
+    }
+}
+
+/// The size of a `width`×`height` image scaled down to fit into the source's bounds.
+fn fitted(source: &Source, width: u32, height: u32) -> Option<(u32, u32)> {
+    let scale = |bound: Option<u16>, length: u32| {
+        bound.map_or(1.0, |bound| f64::from(bound) / f64::from(length.max(1)))
+    };
+    let scale = scale(source.width, width).min(scale(source.height, height));
+    (scale < 1.0).then(|| {
+        (
+            ((f64::from(width) * scale).round() as u32).max(1),
+            ((f64::from(height) * scale).round() as u32).max(1),
+        )
+    })
+}
+
+fn handle(source: &Source, frame: image::RgbaImage) -> Handle {
+    let frame = match fitted(source, frame.width(), frame.height()) {
+        Some((width, height)) => {
+            image::imageops::resize(&frame, width, height, image::imageops::FilterType::Triangle)
+        }
+        None => frame,
+    };
+    Handle::from_rgba(frame.width(), frame.height(), frame.into_raw())
+}
+
+/// Decodes every frame of an animation.
+fn animation<'a>(
+    source: &Source,
+    decoder: impl AnimationDecoder<'a>,
+) -> image::ImageResult<Vec<(Handle, Duration)>> {
+    Ok(decoder
+        .into_frames()
+        .collect_frames()?
+        .into_iter()
+        .map(|frame| {
+            // Like browsers, treat tiny frame delays as unset.
+            let delay = Some(Duration::from(frame.delay()))
+                .filter(|delay| *delay >= Duration::from_millis(20))
+                .unwrap_or(Duration::from_millis(100));
+            (handle(source, frame.into_buffer()), delay)
+        })
+        .collect())
+}
+
+/// The frames of GIF, animated PNG and animated WebP files, going by the file contents rather
+/// than the extension, or `None` for still images.
+fn animated_frames(
+    source: &Source,
+    bytes: &[u8],
+) -> image::ImageResult<Option<Vec<(Handle, Duration)>>> {
+    use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+
+    let cursor = std::io::Cursor::new(bytes);
+    match image::guess_format(bytes) {
+        Ok(image::ImageFormat::Gif) => animation(source, GifDecoder::new(cursor)?).map(Some),
+        Ok(image::ImageFormat::Png) => {
+            let decoder = PngDecoder::new(cursor)?;
+            if decoder.is_apng() {
+                animation(source, decoder.apng()).map(Some)
+            } else {
+                Ok(None)
+            }
+        }
+        Ok(image::ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(cursor)?;
+            if decoder.has_animation() {
+                animation(source, decoder).map(Some)
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+fn decode(source: &Source) -> Result<Picture, String> {
+    let bytes = std::fs::read(&source.path).map_err(|e| e.to_string())?;
+    let frames = match animated_frames(source, &bytes).map_err(|e| e.to_string())? {
+        Some(frames) => frames,
+        None => {
+            let image = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
+            vec![(handle(source, image.into_rgba8()), Duration::ZERO)]
+        }
+    };
+    if frames.is_empty() {
+        return Err("the image has no frames".to_string());
+    }
+    let length = frames.iter().map(|(_, delay)| *delay).sum();
+    Ok(Picture { frames, length })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path: &str, width: Option<u16>, height: Option<u16>) -> Source {
+        Source {
+            path: path.to_string(),
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn scales_down_to_the_bounds_keeping_the_aspect_ratio() {
+        let both = source("", Some(200), Some(100));
+        assert_eq!(fitted(&both, 400, 100), Some((200, 50)));
+        assert_eq!(fitted(&both, 100, 400), Some((25, 100)));
+        assert_eq!(fitted(&both, 300, 150), Some((200, 100)));
+        assert_eq!(
+            fitted(&source("", Some(200), None), 400, 1000),
+            Some((200, 500))
+        );
+        assert_eq!(
+            fitted(&source("", None, Some(100)), 400, 1000),
+            Some((40, 100))
+        );
+        assert_eq!(
+            fitted(&source("", Some(1), Some(1)), 1000, 10),
+            Some((1, 1))
+        );
+    }
+
+    #[test]
+    fn keeps_the_size_of_images_within_the_bounds() {
+        let both = source("", Some(200), Some(100));
+        assert_eq!(fitted(&both, 200, 100), None);
+        assert_eq!(fitted(&both, 20, 10), None);
+        assert_eq!(fitted(&both, 0, 0), None);
+        assert_eq!(fitted(&source("", None, None), 4000, 4000), None);
+    }
+
+    /// Requests `source` and collects the result.
+    fn load(cache: &mut Cache, source: &Source) {
+        iced::futures::executor::block_on(cache.request(source).unwrap());
+        cache.collect();
+    }
+
+    #[test]
+    fn tries_failed_images_again_once_they_change() {
+        let path = std::env::temp_dir().join(format!("tomato-media-{}.png", std::process::id()));
+        let source = source(path.to_str().unwrap(), None, None);
+        let mut cache = Cache::default();
+
+        load(&mut cache, &source);
+        assert!(matches!(cache.status(Some(&source)), Status::Unavailable));
+        std::fs::write(&path, "not an image").unwrap();
+        load(&mut cache, &source);
+        assert!(matches!(cache.status(Some(&source)), Status::Unavailable));
+        assert!(cache.request(&source).is_none());
+
+        image::RgbaImage::new(2, 1).save(&path).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        load(&mut cache, &source);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(cache.status(Some(&source)), Status::Ready(_)));
+        assert!(cache.request(&source).is_none());
+    }
+
+    fn frame(color: [u8; 4]) -> image::RgbaImage {
+        image::RgbaImage::from_pixel(4, 2, image::Rgba(color))
+    }
+
+    fn gif(delays: &[u32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::codecs::gif::GifEncoder::new(&mut bytes)
+            .encode_frames(delays.iter().map(|delay| {
+                let delay = image::Delay::from_numer_denom_ms(*delay, 1);
+                image::Frame::from_parts(frame([255, 0, 0, 255]), 0, 0, delay)
+            }))
+            .unwrap();
+        bytes
+    }
+
+    fn png() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        frame([0, 255, 0, 255])
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        bytes
+    }
+
+    fn webp() -> Vec<u8> {
+        use image::ImageEncoder;
+
+        let mut bytes = Vec::new();
+        image::codecs::webp::WebPEncoder::new_lossless(&mut bytes)
+            .write_image(
+                frame([0, 0, 255, 255]).as_raw(),
+                4,
+                2,
+                image::ColorType::Rgba8,
+            )
+            .unwrap();
+        bytes
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in bytes {
+            crc ^= u32::from(*byte);
+            for _ in 0..8 {
+                crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+            }
+        }
+        !crc
+    }
+
+    fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(kind);
+        chunk.extend(data);
+        chunk.extend(crc32(&chunk[4..]).to_be_bytes());
+        chunk
+    }
+
+    /// A two-frame animated PNG made of the chunks of a still one.
+    fn apng() -> Vec<u8> {
+        let png = png();
+        let (mut chunks, mut rest) = (Vec::new(), &png[8..]);
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            chunks.push((&rest[4..8], &rest[8..8 + length]));
+            rest = &rest[12 + length..];
+        }
+        let data = |kind: &[u8]| chunks.iter().find(|(k, _)| *k == kind).unwrap().1;
+        let frame_control = |sequence: u32| {
+            let mut control = sequence.to_be_bytes().to_vec();
+            control.extend([0, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0]);
+            control.extend([0, 50, 3, 232, 0, 0]);
+            png_chunk(b"fcTL", &control)
+        };
+        let mut frame_data = 2u32.to_be_bytes().to_vec();
+        frame_data.extend(data(b"IDAT"));
+
+        let mut apng = png[..8].to_vec();
+        apng.extend(png_chunk(b"IHDR", data(b"IHDR")));
+        apng.extend(png_chunk(b"acTL", &[0, 0, 0, 2, 0, 0, 0, 0]));
+        apng.extend(frame_control(0));
+        apng.extend(png_chunk(b"IDAT", data(b"IDAT")));
+        apng.extend(frame_control(1));
+        apng.extend(png_chunk(b"fdAT", &frame_data));
+        apng.extend(png_chunk(b"IEND", &[]));
+        apng
+    }
+
+    fn riff_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend((data.len() as u32).to_le_bytes());
+        chunk.extend(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    /// A two-frame animated WebP made of the image data of a still one.
+    fn animated_webp() -> Vec<u8> {
+        let still = webp();
+        let image_data = &still[12..];
+        let mut frame = vec![0, 0, 0, 0, 0, 0, 3, 0, 0, 1, 0, 0, 50, 0, 0, 0];
+        frame.extend(image_data);
+
+        let mut chunks = riff_chunk(b"VP8X", &[2, 0, 0, 0, 3, 0, 0, 1, 0, 0]);
+        chunks.extend(riff_chunk(b"ANIM", &[0, 0, 0, 0, 0, 0]));
+        chunks.extend(riff_chunk(b"ANMF", &frame));
+        chunks.extend(riff_chunk(b"ANMF", &frame));
+        let mut webp = b"RIFF".to_vec();
+        webp.extend((chunks.len() as u32 + 4).to_le_bytes());
+        webp.extend(b"WEBP");
+        webp.extend(chunks);
+        webp
+    }
+
+    fn frame_count(bytes: &[u8]) -> Option<usize> {
+        animated_frames(&source("", None, None), bytes)
+            .unwrap()
+            .map(|frames| frames.len())
+    }
+
+    #[test]
+    fn plays_gifs_and_animated_pngs_and_webps() {
+        assert_eq!(frame_count(&gif(&[50, 50, 50])), Some(3));
+        assert_eq!(frame_count(&apng()), Some(2));
+        assert_eq!(frame_count(&animated_webp()), Some(2));
+    }
+
+    #[test]
+    fn shows_other_images_still() {
+        assert_eq!(frame_count(&png()), None);
+        assert_eq!(frame_count(&webp()), None);
+        assert_eq!(frame_count(b"not an image"), None);
+    }
+
+    /// Decodes `bytes` from a file with the wrong extension, scaled down to 2×1.
+    fn decoded(name: &str, bytes: &[u8]) -> Result<Picture, String> {
+        let path =
+            std::env::temp_dir().join(format!("tomato-media-{}-{name}.jpg", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let picture = decode(&source(path.to_str().unwrap(), Some(2), None));
+        let _ = std::fs::remove_file(&path);
+        picture
+    }
+
+    #[test]
+    fn decodes_by_the_contents() {
+        let size = |picture: &Picture| match picture.frame(Duration::ZERO) {
+            Handle::Rgba { width, height, .. } => (*width, *height),
+            _ => unreachable!(),
+        };
+        for bytes in [gif(&[50, 50]), apng(), animated_webp()] {
+            let picture = decoded("contents", &bytes).unwrap();
+            assert!(picture.is_animated());
+            assert_eq!(picture.length, Duration::from_millis(100));
+            assert_eq!(size(&picture), (2, 1));
+        }
+        for bytes in [png(), webp()] {
+            let picture = decoded("contents", &bytes).unwrap();
+            assert!(!picture.is_animated());
+            assert_eq!(size(&picture), (2, 1));
+        }
+        assert!(decoded("contents", b"not an image").is_err());
+    }
+
+    #[test]
+    fn treats_tiny_frame_delays_as_unset() {
+        let picture = decoded("delays", &gif(&[10, 50])).unwrap();
+        assert_eq!(picture.length, Duration::from_millis(150));
+        let first = picture.frame(Duration::ZERO);
+        assert!(std::ptr::eq(
+            picture.frame(Duration::from_millis(99)),
+            first
+        ));
+        assert!(!std::ptr::eq(
+            picture.frame(Duration::from_millis(100)),
+            first
+        ));
+        assert!(std::ptr::eq(
+            picture.frame(Duration::from_millis(150)),
+            first
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+//! The connected monitors, queried through the X11 RandR extension.
+//!
+//! iced does not expose the monitor layout, so this returns an empty list where RandR is not
+//! available and callers fall back to the monitor the window manager picks. RandR counts
+//! physical pixels, which are turned into the logical ones iced places windows with.
+
+pub struct Monitor {
+    /// The output name, e.g. `DP-1`.
+    pub name: String,
+    pub primary: bool,
+    pub position: iced::Point,
+    pub size: iced::Size,
+}
+
+impl Monitor {
+    /// A monitor from RandR's physical geometry `[x, y, width, height]`, in the logical
+    /// coordinates of windows with `scale_factor`.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fn from_physical(name: String, primary: bool, geometry: [f32; 4], scale_factor: f32) -> Self {
+        let [x, y, width, height] = geometry.map(|value| value / scale_factor);
+        Self {
+            name,
+            primary,
+            position: iced::Point::new(x, y),
+            size: iced::Size::new(width, height),
+        }
+    }
+
+    fn contains(&self, point: iced::Point) -> bool {
+        iced::Rectangle::new(self.position, self.size).contains(point)
+    }
+}
+
+/// The monitor containing `point`.
+pub fn at(monitors: &[Monitor], point: iced::Point) -> Option<&Monitor> {
+    monitors.iter().find(|monitor| monitor.contains(point))
+}
+
+/// The primary monitor, or the first one if none is marked as primary.
+pub fn primary(monitors: &[Monitor]) -> Option<&Monitor> {
+    monitors
+        .iter()
+        .find(|monitor| monitor.primary)
+        .or_else(|| monitors.first())
+}
+
+/// The monitor called `name`, or the primary one if it is not connected.
+pub fn named<'a>(monitors: &'a [Monitor], name: &str) -> Option<&'a Monitor> {
+    monitors
+        .iter()
+        .find(|monitor| monitor.name == name)
+        .or_else(|| primary(monitors))
+}
+
+/// Turns a saved position into screen coordinates. With a monitor name the position is relative
+/// to that monitor and is kept inside it, so the window survives layout changes.
+pub fn place(
+    monitors: &[Monitor],
+    position: Option<[f32; 2]>,
+    monitor: Option<&str>,
+) -> Option<iced::Point> {
+    let [x, y] = position?;
+    let Some(monitor) = monitor.and_then(|name| named(monitors, name)) else {
+        return Some(iced::Point::new(x, y));
+    };
+    Some(iced::Point::new(
+        monitor.position.x + x.clamp(0.0, (monitor.size.width - 1.0).max(0.0)),
+        monitor.position.y + y.clamp(0.0, (monitor.size.height - 1.0).max(0.0)),
+    ))
+}
+
+/// The inverse of [`place`]: the position relative to the monitor containing it.
+pub fn locate(monitors: &[Monitor], point: iced::Point) -> ([f32; 2], Option<String>) {
+    match at(monitors, point) {
+        Some(monitor) => (
+            [point.x - monitor.position.x, point.y - monitor.position.y],
+            Some(monitor.name.clone()),
+        ),
+        None => ([point.x, point.y], None),
+    }
+}
+
+/// The monitors in the logical coordinates of windows with `scale_factor`.
+#[cfg(target_os = "linux")]
+pub fn monitors(scale_factor: f32) -> Vec<Monitor> {
+    use x11rb::{
+        connection::Connection,
+        protocol::{randr, xproto},
+    };
+
+    let query = || -> Option<Vec<Monitor>> {
+        let (connection, screen) = x11rb::connect(None).ok()?;
+        let root = connection.setup().roots[screen].root;
+        let reply = randr::get_monitors(&connection, root, true)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(
+            reply
+                .monitors
+                .iter()
+                .map(|info| {
+                    let name = xproto::get_atom_name(&connection, info.name)
+                        .ok()
+                        .and_then(|cookie| cookie.reply().ok())
+                        .map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+                        .unwrap_or_default();
+                    let geometry = [
+                        f32::from(info.x),
+                        f32::from(info.y),
+                        f32::from(info.width),
+                        f32::from(info.height),
+                    ];
+                    Monitor::from_physical(name, info.primary, geometry, scale_factor)
+                })
+                .collect(),
+        )
+    };
+    query().unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn monitors(_scale_factor: f32) -> Vec<Monitor> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced::Point;
+
+    /// A 1920×1080 monitor on the left and a 2560×1440 primary one on its right, at scale 2.
+    fn layout() -> Vec<Monitor> {
+        vec![
+            Monitor::from_physical("HDMI-1".to_string(), false, [0.0, 0.0, 3840.0, 2160.0], 2.0),
+            Monitor::from_physical("DP-1".to_string(), true, [3840.0, 0.0, 5120.0, 2880.0], 2.0),
+        ]
+    }
+
+    #[test]
+    fn converts_physical_pixels_to_logical_ones() {
+        let monitors = layout();
+        assert_eq!(monitors[1].position, Point::new(1920.0, 0.0));
+        assert_eq!(monitors[1].size, iced::Size::new(2560.0, 1440.0));
+    }
+
+    #[test]
+    fn finds_the_monitor_at_a_point() {
+        let monitors = layout();
+        let name = |point| at(&monitors, point).map(|monitor| monitor.name.as_str());
+        assert_eq!(name(Point::new(0.0, 0.0)), Some("HDMI-1"));
+        assert_eq!(name(Point::new(1919.0, 1079.0)), Some("HDMI-1"));
+        assert_eq!(name(Point::new(1920.0, 0.0)), Some("DP-1"));
+        assert_eq!(name(Point::new(100.0, 1200.0)), None);
+        assert_eq!(primary(&monitors).unwrap().name, "DP-1");
+        assert_eq!(primary(&monitors[..1]).unwrap().name, "HDMI-1");
+        assert_eq!(named(&monitors, "HDMI-1").unwrap().name, "HDMI-1");
+        assert_eq!(named(&monitors, "eDP-1").unwrap().name, "DP-1");
+    }
+
+    #[test]
+    fn places_positions_relative_to_their_monitor() {
+        let monitors = layout();
+        assert_eq!(place(&monitors, None, Some("DP-1")), None);
+        assert_eq!(
+            place(&monitors, Some([30.0, 40.0]), None),
+            Some(Point::new(30.0, 40.0))
+        );
+        assert_eq!(
+            place(&monitors, Some([30.0, 40.0]), Some("DP-1")),
+            Some(Point::new(1950.0, 40.0))
+        );
+        // Kept on a monitor that became smaller, and on the primary one if it is gone.
+        assert_eq!(
+            place(&monitors, Some([5000.0, -10.0]), Some("DP-1")),
+            Some(Point::new(4479.0, 0.0))
+        );
+        assert_eq!(
+            place(&monitors, Some([30.0, 40.0]), Some("eDP-1")),
+            Some(Point::new(1950.0, 40.0))
+        );
+        // Without monitors the position is taken as it is.
+        assert_eq!(
+            place(&[], Some([30.0, 40.0]), Some("DP-1")),
+            Some(Point::new(30.0, 40.0))
+        );
+    }
+
+    #[test]
+    fn locates_what_it_places() {
+        let monitors = layout();
+        let point = Point::new(2000.0, 300.0);
+        let (position, monitor) = locate(&monitors, point);
+        assert_eq!(position, [80.0, 300.0]);
+        assert_eq!(monitor.as_deref(), Some("DP-1"));
+        assert_eq!(
+            place(&monitors, Some(position), monitor.as_deref()),
+            Some(point)
+        );
+        let outside = Point::new(-50.0, 20.0);
+        assert_eq!(locate(&monitors, outside), ([-50.0, 20.0], None));
+    }
+}
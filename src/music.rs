@@ -0,0 +1,269 @@
+//! Pauses and resumes media players over MPRIS when a clock switches between focus and break,
+//! e.g. to stop the music during breaks.
+//!
+//! The players are controlled from a thread of their own, so a slow player never holds up the
+//! clocks. Other platforms than Linux have no MPRIS and ignore the configuration.
+
+use crate::hooks;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerAction {
+    /// Resumes the players paused by the other phase.
+    Play,
+    /// Pauses the players that are playing.
+    Pause,
+    /// Leaves the players alone.
+    Keep,
+}
+
+/// Pauses the music during breaks and resumes it when focusing starts again, unless `focus` or
+/// `break` say otherwise.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct MusicConfig {
+    pub focus: Option<PlayerAction>,
+    #[serde(rename = "break")]
+    pub break_: Option<PlayerAction>,
+    /// Only controls the players whose MPRIS name contains this, e.g. `spotify`.
+    pub player: Option<String>,
+}
+
+pub struct Music {
+    focus: PlayerAction,
+    break_: PlayerAction,
+    commands: mpsc::Sender<PlayerAction>,
+}
+
+impl Music {
+    pub fn new(config: &MusicConfig) -> Self {
+        let (commands, receiver) = mpsc::channel();
+        #[cfg(target_os = "linux")]
+        {
+            let player = config.player.clone();
+            std::thread::spawn(move || mpris::control(player, receiver));
+        }
+        #[cfg(not(target_os = "linux"))]
+        drop(receiver);
+        Self {
+            focus: config.focus.unwrap_or(PlayerAction::Play),
+            break_: config.break_.unwrap_or(PlayerAction::Pause),
+            commands,
+        }
+    }
+
+    /// Plays or pauses the players when `event` starts a focus or break phase of a pomodoro.
+    /// Stopwatches and countdowns to a time of day leave them alone.
+    pub fn follow(&self, event: hooks::Event, details: &hooks::Details) {
+        if details.mode != "pomodoro" {
+            return;
+        }
+        let phase = match event {
+            hooks::Event::BreakStart => "break",
+            hooks::Event::Start | hooks::Event::Resume => details.phase,
+            _ => return,
+        };
+        let action = match phase {
+            "break" => self.break_,
+            _ => self.focus,
+        };
+        if action != PlayerAction::Keep {
+            let _ = self.commands.send(action);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod mpris {
+    use super::PlayerAction;
+    use std::sync::mpsc;
+    use zbus::blocking::{fdo::DBusProxy, Connection, Proxy};
+
+    const PREFIX: &str = "org.mpris.MediaPlayer2.";
+    const PATH: &str = "/org/mpris/MediaPlayer2";
+    const INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+    /// Carries out the actions until the sender is dropped. Only the players paused here are
+    /// resumed, so music the user stopped stays stopped.
+    pub fn control(player: Option<String>, actions: mpsc::Receiver<PlayerAction>) {
+        let mut connection = None;
+        let mut paused: Vec<String> = Vec::new();
+        for action in actions {
+            if connection.is_none() {
+                match Connection::session() {
+                    Ok(session) => connection = Some(session),
+                    Err(e) => {
+                        eprintln!("Failed to connect to the session bus for MPRIS: {e}");
+                        continue;
+                    }
+                }
+            }
+            let Some(session) = &connection else {
+                continue;
+            };
+            let result = match action {
+                PlayerAction::Pause => pause(session, player.as_deref(), &mut paused),
+                PlayerAction::Play => play(session, &mut paused),
+                PlayerAction::Keep => Ok(()),
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to control the media players: {e}");
+                // The bus may have gone away; connect again on the next action.
+                connection = None;
+            }
+        }
+    }
+
+    fn players(session: &Connection, filter: Option<&str>) -> zbus::Result<Vec<String>> {
+        Ok(DBusProxy::new(session)?
+            .list_names()?
+            .into_iter()
+            .map(|name| name.to_string())
+            .filter(|name| {
+                name.strip_prefix(PREFIX)
+                    .is_some_and(|player| filter.is_none_or(|filter| player.contains(filter)))
+            })
+            .collect())
+    }
+
+    fn proxy<'a>(session: &Connection, name: &'a str) -> zbus::Result<Proxy<'a>> {
+        Proxy::new(session, name, PATH, INTERFACE)
+    }
+
+    fn pause(
+        session: &Connection,
+        filter: Option<&str>,
+        paused: &mut Vec<String>,
+    ) -> zbus::Result<()> {
+        for name in players(session, filter)? {
+            let player = proxy(session, &name)?;
+            // A player that fails on its own does not stop the others.
+            let playing = player
+                .get_property::<String>("PlaybackStatus")
+                .is_ok_and(|status| status == "Playing");
+            if playing && player.call_method("Pause", &()).is_ok() && !paused.contains(&name) {
+                paused.push(name.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn play(session: &Connection, paused: &mut Vec<String>) -> zbus::Result<()> {
+        for name in paused.drain(..) {
+            if let Err(e) = proxy(session, &name)?.call_method("Play", &()) {
+                eprintln!("Failed to resume {name}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{pause, play};
+        use crate::test_bus::Bus;
+        use std::sync::{Arc, Mutex};
+        use zbus::blocking::{connection, Connection};
+
+        struct Player {
+            status: Arc<Mutex<&'static str>>,
+        }
+
+        #[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+        impl Player {
+            fn pause(&self) {
+                *self.status.lock().unwrap() = "Paused";
+            }
+
+            fn play(&self) {
+                *self.status.lock().unwrap() = "Playing";
+            }
+
+            #[zbus(property)]
+            fn playback_status(&self) -> String {
+                self.status.lock().unwrap().to_string()
+            }
+        }
+
+        /// Serves a player called `name` on the bus, with its status shared with the test.
+        fn serve(
+            bus: &Bus,
+            name: &str,
+            status: &'static str,
+        ) -> (Connection, Arc<Mutex<&'static str>>) {
+            let status = Arc::new(Mutex::new(status));
+            let player = Player {
+                status: status.clone(),
+            };
+            let connection = connection::Builder::address(bus.address.as_str())
+                .unwrap()
+                .name(format!("org.mpris.MediaPlayer2.{name}"))
+                .unwrap()
+                .serve_at("/org/mpris/MediaPlayer2", player)
+                .unwrap()
+                .build()
+                .unwrap();
+            (connection, status)
+        }
+
+        #[test]
+        fn resumes_only_the_players_it_paused() {
+            let Some(bus) = Bus::start() else {
+                return;
+            };
+            let (_spotify, spotify) = serve(&bus, "spotify", "Playing");
+            let (_vlc, vlc) = serve(&bus, "vlc", "Paused");
+            let (_mpv, mpv) = serve(&bus, "mpv", "Playing");
+            let session = bus.connect();
+            let mut paused = Vec::new();
+
+            pause(&session, Some("o"), &mut paused).unwrap();
+            assert_eq!(*spotify.lock().unwrap(), "Paused");
+            assert_eq!(*mpv.lock().unwrap(), "Playing");
+            assert_eq!(paused, ["org.mpris.MediaPlayer2.spotify"]);
+
+            play(&session, &mut paused).unwrap();
+            assert_eq!(*spotify.lock().unwrap(), "Playing");
+            assert_eq!(*vlc.lock().unwrap(), "Paused");
+            assert!(paused.is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn details(mode: &'static str, phase: &'static str) -> hooks::Details {
+        hooks::Details {
+            mode,
+            phase,
+            planned: None,
+            elapsed: Duration::ZERO,
+            remaining: None,
+            task: None,
+        }
+    }
+
+    #[test]
+    fn follows_pomodoros_only() {
+        let (commands, actions) = mpsc::channel();
+        let music = Music {
+            focus: PlayerAction::Play,
+            break_: PlayerAction::Pause,
+            commands,
+        };
+        music.follow(hooks::Event::Start, &details("stopwatch", "focus"));
+        music.follow(hooks::Event::Resume, &details("until", "focus"));
+        assert!(actions.try_recv().is_err());
+
+        music.follow(hooks::Event::BreakStart, &details("pomodoro", "break"));
+        music.follow(hooks::Event::Pause, &details("pomodoro", "break"));
+        music.follow(hooks::Event::Resume, &details("pomodoro", "focus"));
+        assert_eq!(
+            actions.try_iter().collect::<Vec<_>>(),
+            [PlayerAction::Pause, PlayerAction::Play]
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Working hours per weekday. Outside them the clocks are dimmed or hidden and reminders stay
+//! quiet, and the first pomodoro of a working day can start by itself.
+
+use chrono::{Datelike, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// How long after `auto_start` the first pomodoro still starts, e.g. when the computer wakes
+/// up late.
+const AUTO_START_WINDOW: TimeDelta = TimeDelta::minutes(15);
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outside {
+    #[default]
+    Dim,
+    Hide,
+}
+
+/// Every weekday takes comma-separated ranges such as `"09:00-12:00, 13:00-17:30"`; days that
+/// are left out are days off. A range ending before it starts goes on past midnight.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct WorkHoursConfig {
+    pub monday: Option<String>,
+    pub tuesday: Option<String>,
+    pub wednesday: Option<String>,
+    pub thursday: Option<String>,
+    pub friday: Option<String>,
+    pub saturday: Option<String>,
+    pub sunday: Option<String>,
+    /// What the clocks do outside working hours.
+    pub outside: Option<Outside>,
+    /// Starts the main clock's first pomodoro at this time on working days, e.g. `"09:05"`.
+    pub auto_start: Option<String>,
+}
+
+pub struct Schedule {
+    /// The working hours from Monday to Sunday.
+    days: [Vec<(NaiveTime, NaiveTime)>; 7],
+    pub outside: Outside,
+    auto_start: Option<NaiveTime>,
+}
+
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()
+}
+
+fn parse_ranges(ranges: &str) -> Option<Vec<(NaiveTime, NaiveTime)>> {
+    ranges
+        .split(',')
+        .map(|range| {
+            let (start, end) = range.split_once('-')?;
+            Some((parse_time(start)?, parse_time(end)?))
+        })
+        .collect()
+}
+
+impl Schedule {
+    pub fn new(config: &WorkHoursConfig) -> Self {
+        let days = [
+            &config.monday,
+            &config.tuesday,
+            &config.wednesday,
+            &config.thursday,
+            &config.friday,
+            &config.saturday,
+            &config.sunday,
+        ]
+        .map(|ranges| {
+            let ranges = ranges.as_deref()?;
+            let parsed = parse_ranges(ranges);
+            if parsed.is_none() {
+                eprintln!("Invalid working hours {ranges:?}, expected e.g. \"09:00-17:30\"");
+            }
+            parsed
+        })
+        .map(Option::unwrap_or_default);
+        let auto_start = config.auto_start.as_deref().and_then(|time| {
+            let parsed = parse_time(time);
+            if parsed.is_none() {
+                eprintln!("Invalid auto_start time {time:?}, expected HH:MM");
+            }
+            parsed
+        });
+        Self {
+            days,
+            outside: config.outside.unwrap_or_default(),
+            auto_start,
+        }
+    }
+
+    fn ranges(&self, day: Weekday) -> &[(NaiveTime, NaiveTime)] {
+        &self.days[day.num_days_from_monday() as usize]
+    }
+
+    pub fn working(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        let today = self.ranges(now.weekday()).iter().any(|&(start, end)| {
+            if start < end {
+                start <= time && time < end
+            } else {
+                start <= time
+            }
+        });
+        let from_yesterday = self
+            .ranges(now.weekday().pred())
+            .iter()
+            .any(|&(start, end)| end <= start && time < end);
+        today || from_yesterday
+    }
+
+    /// Whether the first pomodoro of the day should start now.
+    pub fn auto_start_due(&self, now: NaiveDateTime) -> bool {
+        self.auto_start.is_some_and(|auto_start| {
+            let due = now.date().and_time(auto_start);
+            due <= now && now < due + AUTO_START_WINDOW && self.working(now)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule() -> Schedule {
+        Schedule::new(&WorkHoursConfig {
+            monday: Some("09:00-12:00, 13:00-17:30".to_string()),
+            friday: Some("22:00-06:00".to_string()),
+            auto_start: Some("09:05".to_string()),
+            ..WorkHoursConfig::default()
+        })
+    }
+
+    /// A time on the week of Monday, 2 March 2026.
+    fn at(weekday: Weekday, time: &str) -> NaiveDateTime {
+        chrono::NaiveDate::from_isoywd_opt(2026, 10, weekday)
+            .unwrap()
+            .and_time(parse_time(time).unwrap())
+    }
+
+    #[test]
+    fn works_within_the_ranges_of_the_day() {
+        let schedule = schedule();
+        assert!(!schedule.working(at(Weekday::Mon, "08:59")));
+        assert!(schedule.working(at(Weekday::Mon, "09:00")));
+        assert!(!schedule.working(at(Weekday::Mon, "12:00")));
+        assert!(schedule.working(at(Weekday::Mon, "17:29")));
+        assert!(!schedule.working(at(Weekday::Mon, "17:30")));
+        assert!(!schedule.working(at(Weekday::Tue, "10:00")));
+    }
+
+    #[test]
+    fn works_past_midnight_into_the_next_day() {
+        let schedule = schedule();
+        assert!(!schedule.working(at(Weekday::Fri, "21:59")));
+        assert!(schedule.working(at(Weekday::Fri, "23:30")));
+        assert!(schedule.working(at(Weekday::Sat, "05:59")));
+        assert!(!schedule.working(at(Weekday::Sat, "06:00")));
+        // Not on the Friday morning, which follows a day off.
+        assert!(!schedule.working(at(Weekday::Fri, "05:00")));
+    }
+
+    #[test]
+    fn starts_automatically_within_the_window_on_working_days() {
+        let schedule = schedule();
+        assert!(!schedule.auto_start_due(at(Weekday::Mon, "09:04")));
+        assert!(schedule.auto_start_due(at(Weekday::Mon, "09:05")));
+        assert!(schedule.auto_start_due(at(Weekday::Mon, "09:19")));
+        assert!(!schedule.auto_start_due(at(Weekday::Mon, "09:20")));
+        assert!(!schedule.auto_start_due(at(Weekday::Tue, "09:05")));
+    }
+
+    #[test]
+    fn ignores_invalid_ranges() {
+        assert!(parse_ranges("09:00-12:00, 13:00").is_none());
+        assert!(parse_ranges("9-17").is_none());
+        assert_eq!(
+            parse_ranges(" 09:00 - 12:00 ").unwrap(),
+            [(parse_time("09:00").unwrap(), parse_time("12:00").unwrap())]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! [Rhai](https://rhai.rs) scripts for rules the config file cannot express, such as skipping
+//! breaks on Fridays. Every `*.rhai` file in the scripts directory is loaded at startup and can
+//! define these functions, all optional:
+//!
+//! - `on_message(message, clock)`, called with the name of every message, e.g. `"Toggle"`;
+//! - `on_event(event, clock)`, called on the events hooks run on, e.g. `"break_start"`;
+//! - `reminder(clock)`, which returns the text of the next reminder, or `()` to keep it.
+//!
+//! `clock` is a map describing the clock, and `this` a map the script keeps between calls. The
+//! functions act through `start()`, `pause()`, `reset()`, `skip_break()`,
+//! `set_pomodoro_minutes(n)` (5 to 60), `set_break_minutes(n)` (0 to 60, 0 for no breaks) and
+//! `remind(text)`. Scripts cannot reach
+//! files or the network, and run for a limited number of operations with limited memory; their
+//! errors, and what they `print` or `debug`, are reported instead of stopping the program.
+
+use crate::hooks;
+use chrono::{Datelike, Timelike};
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// Stops scripts stuck in a loop.
+const MAX_OPERATIONS: u64 = 100_000;
+/// Stop scripts that keep growing a value before they use up the memory.
+const MAX_STRING_SIZE: usize = 64 * 1024;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+/// Stops runaway recursion.
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+/// The pomodoro lengths scripts can set, as in the config file.
+const POMODORO_MINUTES: std::ops::RangeInclusive<i64> = 5..=60;
+const MAX_BREAK_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    Start,
+    Pause,
+    Reset,
+    SkipBreak,
+    SetPomodoroMinutes(u64),
+    SetBreakMinutes(u64),
+    Remind(String),
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    /// `this` in the script's functions.
+    state: Dynamic,
+}
+
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<Script>,
+    /// The actions taken by the function running, filled by the functions registered with
+    /// the engine.
+    actions: Arc<Mutex<Vec<Action>>>,
+    /// Also filled by the engine with what scripts print.
+    errors: Arc<Mutex<Vec<String>>>,
+}
+
+/// What scripts see of a clock.
+pub struct Clock<'a> {
+    /// The clock's position in the list of clocks; 0 is the main clock.
+    pub index: usize,
+    pub running: bool,
+    pub details: &'a hooks::Details,
+}
+
+impl Clock<'_> {
+    fn map(&self) -> Map {
+        let now = chrono::Local::now();
+        let secs = |duration: Option<std::time::Duration>| {
+            duration.map_or(Dynamic::UNIT, |duration| (duration.as_secs() as i64).into())
+        };
+        let mut map = Map::new();
+        map.insert("index".into(), (self.index as i64).into());
+        map.insert("running".into(), self.running.into());
+        map.insert("mode".into(), self.details.mode.into());
+        map.insert("phase".into(), self.details.phase.into());
+        map.insert("planned_secs".into(), secs(self.details.planned));
+        map.insert("elapsed_secs".into(), secs(Some(self.details.elapsed)));
+        map.insert("remaining_secs".into(), secs(self.details.remaining));
+        map.insert(
+            "task".into(),
+            self.details
+                .task
+                .clone()
+                .map_or(Dynamic::UNIT, Dynamic::from),
+        );
+        map.insert(
+            "weekday".into(),
+            now.weekday().to_string().to_lowercase().into(),
+        );
+        map.insert("hour".into(), i64::from(now.hour()).into());
+        map.insert("minute".into(), i64::from(now.minute()).into());
+        map
+    }
+}
+
+impl Scripts {
+    /// Loads the `*.rhai` files of `dir`, or returns `None` if there are none.
+    pub fn load(dir: &Path) -> Option<Self> {
+        let mut paths = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return None;
+        }
+        paths.sort();
+
+        let actions = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let printed = errors.clone();
+        engine.on_print(move |text| {
+            if let Ok(mut errors) = printed.lock() {
+                errors.push(format!("print: {text}"));
+            }
+        });
+        let printed = errors.clone();
+        engine.on_debug(move |text, source, position| {
+            if let Ok(mut errors) = printed.lock() {
+                errors.push(format!(
+                    "{}debug at {position}: {text}",
+                    source
+                        .map(|source| format!("{source}: "))
+                        .unwrap_or_default()
+                ));
+            }
+        });
+        let register = |engine: &mut Engine, name: &str, action: Action| {
+            let actions = actions.clone();
+            engine.register_fn(name, move || {
+                if let Ok(mut actions) = actions.lock() {
+                    actions.push(action.clone());
+                }
+            });
+        };
+        register(&mut engine, "start", Action::Start);
+        register(&mut engine, "pause", Action::Pause);
+        register(&mut engine, "reset", Action::Reset);
+        register(&mut engine, "skip_break", Action::SkipBreak);
+        let queue = actions.clone();
+        engine.register_fn("set_pomodoro_minutes", move |minutes: i64| {
+            if let Ok(mut actions) = queue.lock() {
+                actions.push(Action::SetPomodoroMinutes(
+                    minutes.clamp(*POMODORO_MINUTES.start(), *POMODORO_MINUTES.end()) as u64,
+                ));
+            }
+        });
+        let queue = actions.clone();
+        engine.register_fn("set_break_minutes", move |minutes: i64| {
+            if let Ok(mut actions) = queue.lock() {
+                actions.push(Action::SetBreakMinutes(
+                    minutes.clamp(0, MAX_BREAK_MINUTES) as u64
+                ));
+            }
+        });
+        let queue = actions.clone();
+        engine.register_fn("remind", move |text: &str| {
+            if let Ok(mut actions) = queue.lock() {
+                actions.push(Action::Remind(text.to_string()));
+            }
+        });
+
+        let scripts = paths
+            .iter()
+            .filter_map(|path| {
+                let name = path.file_name()?.to_string_lossy().to_string();
+                let loaded = engine.compile_file(path.clone()).and_then(|ast| {
+                    // Top-level statements run once, e.g. to set up constants.
+                    engine.run_ast_with_scope(&mut Scope::new(), &ast)?;
+                    Ok(ast)
+                });
+                match loaded {
+                    Ok(ast) => Some(Script {
+                        name,
+                        ast,
+                        state: Map::new().into(),
+                    }),
+                    Err(e) => {
+                        if let Ok(mut errors) = errors.lock() {
+                            errors.push(format!("{name}: {e}"));
+                        }
+                        None
+                    }
+                }
+            })
+            .collect();
+        // Actions taken while loading have no clock to act on.
+        if let Ok(mut actions) = actions.lock() {
+            actions.clear();
+        }
+        Some(Self {
+            engine,
+            scripts,
+            actions,
+            errors,
+        })
+    }
+
+    /// Calls `function` in every script that defines it, returning the actions the scripts
+    /// took and what the last of them returned.
+    fn call(&mut self, function: &str, args: impl Fn() -> Vec<Dynamic>) -> (Vec<Action>, Dynamic) {
+        let arity = args().len();
+        let mut result = Dynamic::UNIT;
+        for script in &mut self.scripts {
+            let defined = script
+                .ast
+                .iter_functions()
+                .any(|f| f.name == function && f.params.len() == arity);
+            if !defined {
+                continue;
+            }
+            let options = CallFnOptions::new()
+                .eval_ast(false)
+                .bind_this_ptr(&mut script.state);
+            match self.engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut Scope::new(),
+                &script.ast,
+                function,
+                args(),
+            ) {
+                Ok(value) => result = value,
+                Err(e) => {
+                    if let Ok(mut errors) = self.errors.lock() {
+                        errors.push(format!("{} in {function}: {e}", script.name));
+                    }
+                }
+            }
+        }
+        let actions = self
+            .actions
+            .lock()
+            .map(|mut actions| std::mem::take(&mut *actions))
+            .unwrap_or_default();
+        (actions, result)
+    }
+
+    pub fn on_message(&mut self, message: &str, clock: &Clock) -> Vec<Action> {
+        let clock = clock.map();
+        self.call("on_message", || vec![message.into(), clock.clone().into()])
+            .0
+    }
+
+    pub fn on_event(&mut self, event: hooks::Event, clock: &Clock) -> Vec<Action> {
+        let clock = clock.map();
+        self.call("on_event", || {
+            vec![event.name().into(), clock.clone().into()]
+        })
+        .0
+    }
+
+    /// The reminder text a script chose, if any. Actions taken by `reminder` are ignored.
+    pub fn reminder(&mut self, clock: &Clock) -> Option<String> {
+        let clock = clock.map();
+        let (_, text) = self.call("reminder", || vec![clock.clone().into()]);
+        text.into_string().ok()
+    }
+
+    /// The errors since the last call, to be shown to the user.
+    pub fn take_errors(&mut self) -> Vec<String> {
+        self.errors
+            .lock()
+            .map(|mut errors| std::mem::take(&mut *errors))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Loads `script` as the only script, named `name`.
+    fn load(name: &str, script: &str) -> Scripts {
+        let dir =
+            std::env::temp_dir().join(format!("tomato-scripts-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("{name}.rhai")), script).unwrap();
+        let scripts = Scripts::load(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        scripts.unwrap()
+    }
+
+    fn details() -> hooks::Details {
+        hooks::Details {
+            mode: "pomodoro",
+            phase: "focus",
+            planned: Some(Duration::from_secs(25 * 60)),
+            elapsed: Duration::ZERO,
+            remaining: Some(Duration::from_secs(25 * 60)),
+            task: None,
+        }
+    }
+
+    #[test]
+    fn clamps_the_durations_scripts_set() {
+        let mut scripts = load(
+            "durations",
+            "fn on_message(message, clock) {
+                set_pomodoro_minutes(9223372036854775807);
+                set_pomodoro_minutes(0);
+                set_break_minutes(100000);
+                set_break_minutes(-5);
+            }",
+        );
+        let details = details();
+        let clock = Clock {
+            index: 0,
+            running: false,
+            details: &details,
+        };
+        let actions = scripts.on_message("Toggle", &clock);
+        assert!(scripts.take_errors().is_empty());
+        assert!(matches!(
+            actions.as_slice(),
+            [
+                Action::SetPomodoroMinutes(60),
+                Action::SetPomodoroMinutes(5),
+                Action::SetBreakMinutes(60),
+                Action::SetBreakMinutes(0),
+            ]
+        ));
+    }
+
+    #[test]
+    fn stops_scripts_growing_without_bound() {
+        let mut scripts = load(
+            "runaway",
+            "print(\"loaded\");
+            fn on_message(message, clock) {
+                let text = \"tomato\";
+                loop { text += text; }
+            }
+            fn on_event(event, clock) {
+                let list = [1];
+                loop { list += list; }
+            }
+            fn reminder(clock) { reminder(clock) }",
+        );
+        assert_eq!(scripts.take_errors(), ["print: loaded"]);
+        let details = details();
+        let clock = Clock {
+            index: 0,
+            running: true,
+            details: &details,
+        };
+        assert!(scripts.on_message("Toggle", &clock).is_empty());
+        scripts.on_event(hooks::Event::Start, &clock);
+        assert_eq!(scripts.reminder(&clock), None);
+        let errors = scripts.take_errors();
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert!(errors[0].contains("Length of string"), "{}", errors[0]);
+        assert!(errors[1].contains("Size of array"), "{}", errors[1]);
+        assert!(errors[2].contains("Stack overflow"), "{}", errors[2]);
+    }
+}
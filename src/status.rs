@@ -0,0 +1,520 @@
+//! `tomato-clock status` and `tomato-clock action`, which talk to a running clock over its HTTP
+//! API to show it in a status bar and control it from the bar's clicks.
+
+use crate::{
+    api::{self, ApiConfig},
+    http,
+};
+use serde::Deserialize;
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::ExitCode,
+    time::Duration,
+};
+
+/// How long to wait before connecting again to a clock that is not running.
+const RETRY: Duration = Duration::from_secs(5);
+const NOT_RUNNING: &str = "tomato-clock is not running or its [api] is not enabled";
+
+const USAGE: &str = "\
+Usage:
+  tomato-clock status [--follow] [--format json|waybar|i3bar|text] [--clock N]
+  tomato-clock action toggle|start|pause|reset|skip_break|postpone_break|lap|longer|shorter [--clock N]
+
+Both read the [api] table of tomato.toml; --listen ADDRESS and --token TOKEN override it.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Waybar,
+    I3bar,
+    Text,
+}
+
+#[derive(Clone, Debug)]
+struct Options {
+    follow: bool,
+    format: Format,
+    clock: usize,
+    listen: String,
+    token: Option<String>,
+    /// What is left after the options, e.g. the action.
+    rest: Vec<String>,
+}
+
+/// The part of the config file the commands need.
+#[derive(Deserialize, Default)]
+struct Config {
+    api: Option<ApiConfig>,
+}
+
+/// What the commands read of a clock in the API's state.
+#[derive(Deserialize)]
+struct Clock {
+    label: Option<String>,
+    phase: String,
+    running: bool,
+    display: String,
+    planned_secs: Option<u64>,
+    elapsed_secs: u64,
+    task: Option<String>,
+}
+
+/// The `[api]` table of the config file in the startup directory.
+fn api_config() -> ApiConfig {
+    std::fs::read_to_string(crate::CONFIG_PATH)
+        .ok()
+        .and_then(|config| toml::from_str::<Config>(&config).ok())
+        .and_then(|config| config.api)
+        .unwrap_or_default()
+}
+
+fn parse_options(args: &[String], config: ApiConfig) -> Result<Options, String> {
+    let mut options = Options {
+        follow: false,
+        format: Format::Json,
+        clock: 0,
+        listen: config
+            .listen
+            .unwrap_or_else(|| api::DEFAULT_LISTEN.to_string()),
+        token: config.token,
+        rest: Vec::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or(format!("{name} needs a value"))
+        };
+        match name {
+            "--follow" | "-f" => options.follow = true,
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "json" => Format::Json,
+                    "waybar" => Format::Waybar,
+                    "i3bar" => Format::I3bar,
+                    "text" => Format::Text,
+                    other => return Err(format!("Unknown format {other:?}")),
+                }
+            }
+            "--clock" => {
+                options.clock = value()?
+                    .parse()
+                    .map_err(|_| "--clock needs a number".to_string())?
+            }
+            "--listen" => options.listen = value()?,
+            "--token" => options.token = Some(value()?),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ if name.starts_with('-') => return Err(format!("Unknown option {name}\n\n{USAGE}")),
+            _ => options.rest.push(arg.clone()),
+        }
+    }
+    Ok(options)
+}
+
+/// Sends a request and returns the response with its head read, or an error if the status is
+/// not a success.
+fn request(options: &Options, method: &str, path: &str) -> io::Result<BufReader<TcpStream>> {
+    let mut stream = TcpStream::connect(&options.listen)?;
+    let authorization = options
+        .token
+        .as_ref()
+        .map(|token| format!("Authorization: Bearer {token}\r\n"))
+        .unwrap_or_default();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {}\r\n{authorization}Content-Length: 0\r\n\
+         Connection: close\r\n\r\n",
+        options.listen
+    )?;
+    stream.set_read_timeout(Some(http::HEAD_TIMEOUT))?;
+    let mut response = BufReader::new(stream);
+    let head = http::read_head(&mut response)?;
+    response.get_ref().set_read_timeout(None)?;
+    if !head
+        .start_line
+        .split_whitespace()
+        .nth(1)
+        .is_some_and(|code| code.starts_with('2'))
+    {
+        let mut body = String::new();
+        let _ = response.read_to_string(&mut body);
+        return Err(io::Error::other(format!(
+            "{} {}",
+            head.start_line,
+            body.trim()
+        )));
+    }
+    Ok(response)
+}
+
+/// Asks the clock to take `action`.
+fn send(options: &Options, action: &str) -> io::Result<()> {
+    request(
+        options,
+        "POST",
+        &format!("/clocks/{}/{action}", options.clock),
+    )
+    .map(drop)
+}
+
+/// The line a status bar shows for `state`, the API's `{"clocks": [...]}`.
+fn render(format: Format, clock: usize, state: &serde_json::Value) -> String {
+    let value = &state["clocks"][clock];
+    let Ok(status) = Clock::deserialize(value) else {
+        return render_missing(format, &format!("no clock {clock}"));
+    };
+    let text = match &status.label {
+        Some(label) => format!("{label} {}", status.display),
+        None => status.display.clone(),
+    };
+    let state_class = if status.running { "running" } else { "paused" };
+    match format {
+        Format::Json => value.to_string(),
+        Format::Text => text,
+        Format::Waybar => {
+            let percentage = status
+                .planned_secs
+                .filter(|planned| *planned > 0)
+                .map(|planned| (status.elapsed_secs * 100 / planned).min(100));
+            let tooltip = [Some(status.phase.clone()), status.task.clone()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" · ");
+            serde_json::json!({
+                "text": text,
+                "alt": status.phase,
+                "tooltip": tooltip,
+                "class": [status.phase, state_class],
+                "percentage": percentage,
+            })
+            .to_string()
+        }
+        Format::I3bar => {
+            let block = serde_json::json!({
+                "name": "tomato-clock",
+                "instance": clock.to_string(),
+                "full_text": text,
+                "short_text": status.display,
+            });
+            format!("[{block}],")
+        }
+    }
+}
+
+/// Why the clock's API could not be read.
+fn unreachable_reason(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused => NOT_RUNNING.to_string(),
+        _ => format!("Failed to read the clock: {e}"),
+    }
+}
+
+/// The line shown while the clock cannot be reached.
+fn render_missing(format: Format, reason: &str) -> String {
+    match format {
+        Format::Json => serde_json::json!({ "error": reason }).to_string(),
+        Format::Text => String::new(),
+        Format::Waybar => {
+            serde_json::json!({ "text": "", "tooltip": reason, "class": "stopped" }).to_string()
+        }
+        Format::I3bar => "[],".to_string(),
+    }
+}
+
+/// Prints a line for every `state` event until the stream ends.
+fn follow(options: &Options, out: &mut impl Write) -> io::Result<()> {
+    let events = request(options, "GET", "/events")?;
+    let mut event = String::new();
+    for line in events.lines() {
+        let line = line?;
+        if let Some(name) = line.strip_prefix("event:") {
+            event = name.trim().to_string();
+        } else if let Some(data) = line.strip_prefix("data:") {
+            if event == "state" {
+                if let Ok(state) = serde_json::from_str(data.trim()) {
+                    writeln!(out, "{}", render(options.format, options.clock, &state))?;
+                    out.flush()?;
+                }
+            }
+        } else if line.is_empty() {
+            event.clear();
+        }
+    }
+    Ok(())
+}
+
+/// Reads i3bar click events from standard input and runs the actions they are mapped to: left
+/// click toggles, middle click skips the break and right click resets; scrolling changes the
+/// pomodoro length.
+fn forward_clicks(options: Options) {
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            // Click events come as an endless JSON array, one element per line.
+            let click = line.trim().trim_start_matches(['[', ',']);
+            let Ok(click) = serde_json::from_str::<serde_json::Value>(click) else {
+                continue;
+            };
+            let name = match click["button"].as_u64() {
+                Some(1) => "toggle",
+                Some(2) => "skip_break",
+                Some(3) => "reset",
+                Some(4) => "longer",
+                Some(5) => "shorter",
+                _ => continue,
+            };
+            if let Err(e) = send(&options, name) {
+                eprintln!("Failed to {name}: {e}");
+            }
+        }
+    });
+}
+
+/// `tomato-clock status`.
+pub fn status(args: &[String]) -> ExitCode {
+    let options = match parse_options(args, api_config()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = io::stdout().lock();
+    if !options.follow {
+        let state = request(&options, "GET", "/state").and_then(|mut response| {
+            let mut body = String::new();
+            response.read_to_string(&mut body)?;
+            serde_json::from_str(&body).map_err(io::Error::other)
+        });
+        return match state {
+            Ok(state) => {
+                let line = render(options.format, options.clock, &state);
+                let _ = writeln!(out, "{}", line.trim_end_matches(','));
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("{}", unreachable_reason(&e));
+                let _ = writeln!(
+                    out,
+                    "{}",
+                    render_missing(options.format, &unreachable_reason(&e))
+                );
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if options.format == Format::I3bar {
+        let _ = writeln!(out, "{{\"version\":1,\"click_events\":true}}\n[");
+        forward_clicks(options.clone());
+    }
+    // Keep the bar's module alive across restarts of the clock.
+    loop {
+        let result = follow(&options, &mut out);
+        if let Err(e) = &result {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                return ExitCode::SUCCESS;
+            }
+        }
+        let reason = match result {
+            Ok(()) => NOT_RUNNING.to_string(),
+            Err(e) => unreachable_reason(&e),
+        };
+        if writeln!(out, "{}", render_missing(options.format, &reason))
+            .and_then(|()| out.flush())
+            .is_err()
+        {
+            return ExitCode::SUCCESS;
+        }
+        std::thread::sleep(RETRY);
+    }
+}
+
+/// `tomato-clock action`.
+pub fn action(args: &[String]) -> ExitCode {
+    let options = match parse_options(args, api_config()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let [name] = options.rest.as_slice() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    match send(&options, name) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Failed to {name}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let config = ApiConfig {
+            listen: Some("127.0.0.1:9000".to_string()),
+            token: Some("from-config".to_string()),
+            ..ApiConfig::default()
+        };
+        parse_options(&args, config)
+    }
+
+    #[test]
+    fn parses_options_over_the_config() {
+        let options = parse(&["--follow", "--format=waybar", "--clock", "2", "toggle"]).unwrap();
+        assert!(options.follow);
+        assert_eq!(options.format, Format::Waybar);
+        assert_eq!(options.clock, 2);
+        assert_eq!(options.listen, "127.0.0.1:9000");
+        assert_eq!(options.token.as_deref(), Some("from-config"));
+        assert_eq!(options.rest, ["toggle"]);
+
+        let options = parse(&["--listen", "127.0.0.1:7245", "--token=secret"]).unwrap();
+        assert_eq!(options.listen, "127.0.0.1:7245");
+        assert_eq!(options.token.as_deref(), Some("secret"));
+        assert_eq!(options.format, Format::Json);
+
+        let defaults = parse_options(&[], ApiConfig::default()).unwrap();
+        assert_eq!(defaults.listen, api::DEFAULT_LISTEN);
+        assert_eq!(defaults.token, None);
+    }
+
+    #[test]
+    fn refuses_bad_options() {
+        assert_eq!(parse(&["--clock"]).unwrap_err(), "--clock needs a value");
+        assert_eq!(
+            parse(&["--clock", "x"]).unwrap_err(),
+            "--clock needs a number"
+        );
+        assert_eq!(
+            parse(&["--format=xml"]).unwrap_err(),
+            "Unknown format \"xml\""
+        );
+        assert!(parse(&["--verbose"])
+            .unwrap_err()
+            .starts_with("Unknown option --verbose"));
+        assert_eq!(parse(&["-h"]).unwrap_err(), USAGE);
+    }
+
+    fn state(elapsed_secs: u64) -> serde_json::Value {
+        serde_json::json!({ "clocks": [{
+            "index": 0,
+            "label": "Work",
+            "mode": "pomodoro",
+            "phase": "focus",
+            "running": true,
+            "display": "12:30",
+            "planned_secs": 1500,
+            "elapsed_secs": elapsed_secs,
+            "remaining_secs": 750,
+            "task": "Work",
+        }]})
+    }
+
+    #[test]
+    fn renders_a_clock_in_every_format() {
+        let state = state(750);
+        assert_eq!(render(Format::Text, 0, &state), "Work 12:30");
+        assert_eq!(
+            render(Format::Json, 0, &state),
+            state["clocks"][0].to_string()
+        );
+        let waybar: serde_json::Value =
+            serde_json::from_str(&render(Format::Waybar, 0, &state)).unwrap();
+        assert_eq!(waybar["text"], "Work 12:30");
+        assert_eq!(waybar["alt"], "focus");
+        assert_eq!(waybar["tooltip"], "focus · Work");
+        assert_eq!(waybar["class"], serde_json::json!(["focus", "running"]));
+        assert_eq!(waybar["percentage"], 50);
+        let i3bar = render(Format::I3bar, 0, &state);
+        assert!(i3bar.starts_with('[') && i3bar.ends_with("],"));
+        let blocks: serde_json::Value = serde_json::from_str(&i3bar[..i3bar.len() - 1]).unwrap();
+        assert_eq!(blocks[0]["full_text"], "Work 12:30");
+        assert_eq!(blocks[0]["short_text"], "12:30");
+    }
+
+    #[test]
+    fn keeps_the_percentage_in_range() {
+        let waybar = render(Format::Waybar, 0, &state(4000));
+        let waybar: serde_json::Value = serde_json::from_str(&waybar).unwrap();
+        assert_eq!(waybar["percentage"], 100);
+    }
+
+    #[test]
+    fn renders_missing_clocks() {
+        let state = state(0);
+        assert_eq!(render(Format::Json, 3, &state), r#"{"error":"no clock 3"}"#);
+        assert_eq!(render(Format::Text, 3, &state), "");
+        assert_eq!(render(Format::I3bar, 3, &state), "[],");
+        let waybar: serde_json::Value =
+            serde_json::from_str(&render_missing(Format::Waybar, NOT_RUNNING)).unwrap();
+        assert_eq!(waybar["class"], "stopped");
+        assert_eq!(waybar["tooltip"], NOT_RUNNING);
+    }
+
+    #[test]
+    fn follows_the_state_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut options = parse(&["--format", "text"]).unwrap();
+        options.listen = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let head = http::read_head(&mut BufReader::new(&client)).unwrap();
+            assert_eq!(head.start_line, "GET /events HTTP/1.1");
+            assert_eq!(head.header("authorization"), Some("Bearer from-config"));
+            let first = state(0);
+            let second = state(60);
+            write!(
+                client,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n\
+                 event: state\ndata: {first}\n\n\
+                 : keep-alive\n\n\
+                 event: start\ndata: {{\"clock\": 0}}\n\n\
+                 event: state\ndata: {second}\n\n"
+            )
+            .unwrap();
+        });
+        let mut out = Vec::new();
+        follow(&options, &mut out).unwrap();
+        server.join().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "Work 12:30\nWork 12:30\n");
+    }
+
+    #[test]
+    fn reports_refused_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut options = parse(&[]).unwrap();
+        options.listen = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            http::read_head(&mut BufReader::new(&client)).unwrap();
+            client
+                .write_all(b"HTTP/1.1 401 Unauthorized\r\n\r\n{\"error\":\"wrong token\"}")
+                .unwrap();
+        });
+        let error = send(&options, "toggle").unwrap_err();
+        server.join().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "HTTP/1.1 401 Unauthorized {\"error\":\"wrong token\"}"
+        );
+    }
+}
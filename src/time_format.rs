@@ -0,0 +1,184 @@
+//! Renders clock durations from format strings such as `%M:%S` or `%m min left`.
+//!
+//! `%H`, `%M` and `%S` are the zero-padded hours, minutes of the hour and seconds of the minute;
+//! `%h`, `%m` and `%s` are the total hours, minutes and seconds; `%f` is the tenths of a second
+//! and `%%` a percent sign. `auto` is `%M:%S` under an hour and `%H:%M:%S` from an hour on.
+
+use crate::Mode;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const AUTO: &str = "auto";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TimeFormat {
+    /// The format of pomodoros, breaks and `until` countdowns, `auto` if unset.
+    pub pomodoro: Option<String>,
+    /// The format of stopwatches, `auto` if unset.
+    pub stopwatch: Option<String>,
+    /// Drops `%S`, `%s` and `%f` together with the separator in front of them while the clock
+    /// is running. Minutes left without hours get a ` min` unit instead, so `%M:%S` shows
+    /// `25 min`.
+    pub hide_seconds_while_running: bool,
+}
+
+impl TimeFormat {
+    fn pattern(&self, mode: Mode) -> &str {
+        match mode {
+            Mode::Pomodoro | Mode::Until => self.pomodoro.as_deref(),
+            Mode::Stopwatch => self.stopwatch.as_deref(),
+        }
+        .unwrap_or(AUTO)
+    }
+
+    pub fn render(&self, duration: Duration, mode: Mode, running: bool) -> String {
+        format(
+            duration,
+            self.pattern(mode),
+            running && self.hide_seconds_while_running,
+        )
+    }
+
+    /// Whether a running clock changes its text more often than once a second.
+    pub fn shows_tenths(&self, mode: Mode, running: bool) -> bool {
+        running && !self.hide_seconds_while_running && self.pattern(mode).contains("%f")
+    }
+}
+
+enum Segment {
+    Literal(String),
+    Field(char),
+}
+
+fn parse(pattern: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('%', Some(field @ ('H' | 'M' | 'S' | 'h' | 'm' | 's' | 'f'))) => {
+                chars.next();
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Field(field));
+            }
+            ('%', Some('%')) => {
+                chars.next();
+                literal.push('%');
+            }
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    segments
+}
+
+/// Renders `duration` with `pattern`, leaving out the seconds if `hide_seconds` is set.
+pub fn format(duration: Duration, pattern: &str, hide_seconds: bool) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = MINUTE * 60;
+
+    let seconds = duration.as_secs();
+    let pattern = match pattern {
+        AUTO if seconds < HOUR && hide_seconds => "%m min",
+        AUTO if seconds < HOUR => "%M:%S",
+        AUTO => "%H:%M:%S",
+        pattern => pattern,
+    };
+    let mut segments = parse(pattern);
+    if hide_seconds {
+        // `%H:%M` reads as a time without a unit; a lone `%M` does not.
+        let unit = !segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field('H' | 'h')));
+        let mut kept: Vec<Segment> = Vec::with_capacity(segments.len());
+        for segment in segments {
+            if let Segment::Field('S' | 's' | 'f') = segment {
+                if let [.., _, Segment::Literal(separator)] = kept.as_slice() {
+                    if separator.chars().count() == 1 {
+                        kept.pop();
+                    }
+                }
+                if unit && matches!(kept.last(), Some(Segment::Field('M' | 'm'))) {
+                    kept.push(Segment::Literal(" min".to_string()));
+                }
+                continue;
+            }
+            kept.push(segment);
+        }
+        segments = kept;
+    }
+    segments
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Literal(literal) => literal,
+            Segment::Field('H') => format!("{:0>2}", seconds / HOUR),
+            Segment::Field('M') => format!("{:0>2}", seconds % HOUR / MINUTE),
+            Segment::Field('S') => format!("{:0>2}", seconds % MINUTE),
+            Segment::Field('h') => (seconds / HOUR).to_string(),
+            Segment::Field('m') => (seconds / MINUTE).to_string(),
+            Segment::Field('s') => seconds.to_string(),
+            Segment::Field(_) => (duration.subsec_millis() / 100).to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWENTY_FIVE: Duration = Duration::from_secs(25 * 60);
+
+    #[test]
+    fn renders_fields() {
+        let duration = Duration::from_millis((3600 + 2 * 60 + 5) * 1000 + 700);
+        assert_eq!(format(duration, "%H:%M:%S.%f", false), "01:02:05.7");
+        assert_eq!(
+            format(duration, "%h h %m min %s s", false),
+            "1 h 62 min 3725 s"
+        );
+        assert_eq!(format(duration, "100%% %M", false), "100% 02");
+        assert_eq!(format(duration, "%x %", false), "%x %");
+    }
+
+    #[test]
+    fn auto_drops_the_hours_under_an_hour() {
+        assert_eq!(
+            format(Duration::from_secs(24 * 60 + 59), AUTO, false),
+            "24:59"
+        );
+        assert_eq!(format(Duration::from_secs(3600), AUTO, false), "01:00:00");
+        assert_eq!(format(TWENTY_FIVE, AUTO, true), "25 min");
+        assert_eq!(format(Duration::from_secs(3600), AUTO, true), "01:00");
+    }
+
+    #[test]
+    fn hiding_the_seconds_keeps_the_minutes_readable() {
+        assert_eq!(format(TWENTY_FIVE, "%M:%S", true), "25 min");
+        assert_eq!(format(TWENTY_FIVE, "%M:%S left", true), "25 min left");
+        assert_eq!(format(TWENTY_FIVE, "%m:%S.%f", true), "25 min");
+        assert_eq!(format(TWENTY_FIVE, "%m min left", true), "25 min left");
+        assert_eq!(format(TWENTY_FIVE, "%H:%M:%S", true), "00:25");
+        assert_eq!(format(TWENTY_FIVE, "%M:%S", false), "25:00");
+    }
+
+    #[test]
+    fn hides_the_seconds_only_while_running() {
+        let format = TimeFormat {
+            pomodoro: Some("%M:%S".to_string()),
+            stopwatch: Some("%M:%S.%f".to_string()),
+            hide_seconds_while_running: true,
+        };
+        assert_eq!(format.render(TWENTY_FIVE, Mode::Pomodoro, true), "25 min");
+        assert_eq!(format.render(TWENTY_FIVE, Mode::Pomodoro, false), "25:00");
+        assert!(!format.shows_tenths(Mode::Stopwatch, true));
+        assert_eq!(
+            TimeFormat::default().render(TWENTY_FIVE, Mode::Stopwatch, true),
+            "25:00"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Colors that change as a pomodoro runs out, so the clock shows urgency without reading the
+//! digits.
+
+use crate::Color;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Recolors a running pomodoro once `seconds_left` is reached, e.g. amber at 300.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UrgencyRule {
+    pub seconds_left: u64,
+    pub text: Option<Color>,
+    pub background: Option<Color>,
+    /// Fades into the colors until the next rule's threshold instead of switching at once.
+    #[serde(default)]
+    pub gradient: bool,
+    /// Pulses the text once a second.
+    #[serde(default)]
+    pub pulse: bool,
+}
+
+fn mix(from: iced::Color, to: iced::Color, amount: f32) -> iced::Color {
+    let channel = |from: f32, to: f32| from + (to - from) * amount;
+    iced::Color::from_rgba(
+        channel(from.r, to.r),
+        channel(from.g, to.g),
+        channel(from.b, to.b),
+        channel(from.a, to.a),
+    )
+}
+
+/// The rules in effect with `remaining` time left, from the earliest threshold to the latest.
+fn active(rules: &[UrgencyRule], remaining: Duration) -> Vec<&UrgencyRule> {
+    let mut rules = rules.iter().collect::<Vec<_>>();
+    rules.sort_by_key(|rule| std::cmp::Reverse(rule.seconds_left));
+    rules.retain(|rule| remaining.as_secs_f32() <= rule.seconds_left as f32);
+    rules
+}
+
+/// Whether the text pulses, which needs more than one frame a second.
+pub fn pulsing(rules: &[UrgencyRule], remaining: Duration) -> bool {
+    active(rules, remaining)
+        .last()
+        .is_some_and(|rule| rule.pulse)
+}
+
+/// The text and background colors with `remaining` time left.
+pub fn apply(
+    rules: &[UrgencyRule],
+    remaining: Duration,
+    mut text: iced::Color,
+    mut background: iced::Color,
+) -> (iced::Color, iced::Color) {
+    let remaining_secs = remaining.as_secs_f32();
+    let active = active(rules, remaining);
+    for (i, rule) in active.iter().enumerate() {
+        let amount = if rule.gradient {
+            let end = rules
+                .iter()
+                .map(|other| other.seconds_left)
+                .filter(|seconds| *seconds < rule.seconds_left)
+                .max()
+                .unwrap_or(0) as f32;
+            let span = (rule.seconds_left as f32 - end).max(1.0);
+            ((rule.seconds_left as f32 - remaining_secs) / span).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        if let Some(color) = rule.text {
+            text = mix(text, color.into(), amount);
+        }
+        if let Some(color) = rule.background {
+            background = mix(background, color.into(), amount);
+        }
+        if rule.pulse && i == active.len() - 1 {
+            let phase = remaining.subsec_millis() as f32 / 1000.0;
+            text.a *= 0.6 + 0.4 * (phase * std::f32::consts::TAU).cos();
+        }
+    }
+    (text, background)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: iced::Color = iced::Color::WHITE;
+    const BLACK: iced::Color = iced::Color::BLACK;
+
+    fn rule(seconds_left: u64, text: [f32; 3], gradient: bool, pulse: bool) -> UrgencyRule {
+        let [r, g, b] = text;
+        UrgencyRule {
+            seconds_left,
+            text: Some(Color { r, g, b, a: 1.0 }),
+            background: None,
+            gradient,
+            pulse,
+        }
+    }
+
+    fn text(rules: &[UrgencyRule], remaining_secs: f32) -> iced::Color {
+        apply(rules, Duration::from_secs_f32(remaining_secs), WHITE, BLACK).0
+    }
+
+    fn assert_close(color: iced::Color, [r, g, b]: [f32; 3]) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        assert!(
+            close(color.r, r) && close(color.g, g) && close(color.b, b),
+            "{color:?} is not {r} {g} {b}"
+        );
+    }
+
+    #[test]
+    fn starts_at_the_threshold() {
+        let rules = [rule(300, [1.0, 0.5, 0.0], false, false)];
+        assert_close(text(&rules, 301.0), [1.0, 1.0, 1.0]);
+        assert_close(text(&rules, 300.0), [1.0, 0.5, 0.0]);
+        assert_close(text(&rules, 0.0), [1.0, 0.5, 0.0]);
+        let (_, background) = apply(&rules, Duration::ZERO, WHITE, BLACK);
+        assert_eq!(background, BLACK);
+    }
+
+    #[test]
+    fn fades_in_gradients() {
+        let rules = [rule(300, [0.0, 0.0, 0.0], true, false)];
+        assert_close(text(&rules, 300.0), [1.0, 1.0, 1.0]);
+        assert_close(text(&rules, 150.0), [0.5, 0.5, 0.5]);
+        assert_close(text(&rules, 0.0), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn applies_later_rules_over_earlier_ones() {
+        // Given out of order, with the amber rule fading until the red one starts.
+        let rules = [
+            rule(60, [1.0, 0.0, 0.0], false, false),
+            rule(300, [1.0, 0.5, 0.0], true, false),
+        ];
+        assert_close(text(&rules, 180.0), [1.0, 0.75, 0.5]);
+        assert_close(text(&rules, 61.0), [1.0, 0.50208336, 0.004166667]);
+        assert_close(text(&rules, 60.0), [1.0, 0.0, 0.0]);
+        assert_close(text(&rules, 10.0), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn pulses_only_with_the_latest_rule() {
+        let rules = [
+            rule(300, [1.0, 1.0, 1.0], false, true),
+            rule(60, [1.0, 1.0, 1.0], false, false),
+        ];
+        assert!(!pulsing(&rules, Duration::from_secs(400)));
+        assert!(pulsing(&rules, Duration::from_secs(200)));
+        assert!(!pulsing(&rules, Duration::from_secs(30)));
+        let alpha = |remaining| apply(&rules, remaining, WHITE, BLACK).0.a;
+        assert!((alpha(Duration::from_secs(200)) - 1.0).abs() < 1e-4);
+        assert!((alpha(Duration::from_millis(200_500)) - 0.2).abs() < 1e-4);
+        assert_eq!(alpha(Duration::from_millis(30_500)), 1.0);
+    }
+}